@@ -0,0 +1,70 @@
+//! Compares solver::propagate_state, which reruns every rule on every call,
+//! against the incremental solver, as accusations are added one at a time.
+//!
+//! Run with `cargo bench --features simulation`.
+
+use std::time::{Duration, Instant};
+
+use cluesolverlib::accusation::Accusation;
+use cluesolverlib::game_state::GameState;
+use cluesolverlib::incremental::IncrementalSolver;
+use cluesolverlib::simulation::simulate_game;
+use cluesolverlib::solver::propagate_state;
+
+const NUMBER_OF_GAMES: u64 = 20;
+const NUMBER_OF_ACCUSATIONS: usize = 30;
+
+/// Propagates the game from scratch after every accusation
+fn time_propagate_state(gs: &GameState, accusations: &[Accusation]) -> Duration {
+    let start = Instant::now();
+
+    let mut game_so_far = gs.clone();
+
+    for accusation in accusations {
+        game_so_far.add_accusation(accusation.clone());
+
+        let mut propagated = game_so_far.clone();
+        propagate_state(&mut propagated).unwrap();
+    }
+
+    start.elapsed()
+}
+
+/// Adds each accusation to the incremental solver
+fn time_incremental(gs: &GameState, accusations: &[Accusation]) -> Duration {
+    let start = Instant::now();
+
+    let mut solver = IncrementalSolver::new(gs.clone()).unwrap();
+
+    for accusation in accusations {
+        solver.add_accusation(accusation.clone()).unwrap();
+    }
+
+    start.elapsed()
+}
+
+fn main() {
+    println!("{} games of {} accusations each, propagated after every accusation\n",
+        NUMBER_OF_GAMES, NUMBER_OF_ACCUSATIONS);
+
+    println!("{:<10}{:>18}{:>18}{:>10}", "Players", "propagate_state", "incremental", "Speedup");
+
+    for number_of_players in 3..=6 {
+        let mut propagate_state_time = Duration::ZERO;
+        let mut incremental_time = Duration::ZERO;
+
+        for seed in 0..NUMBER_OF_GAMES {
+            let mut gs = simulate_game(number_of_players, seed, NUMBER_OF_ACCUSATIONS);
+            let accusations = std::mem::take(&mut gs.accusations);
+
+            propagate_state_time += time_propagate_state(&gs, &accusations);
+            incremental_time += time_incremental(&gs, &accusations);
+        }
+
+        println!("{:<10}{:>18}{:>18}{:>9.1}x",
+            number_of_players,
+            format!("{:.2?}", propagate_state_time),
+            format!("{:.2?}", incremental_time),
+            propagate_state_time.as_secs_f64() / incremental_time.as_secs_f64());
+    }
+}
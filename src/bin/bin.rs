@@ -1,14 +1,27 @@
-use clap::Command;
-use cluesolverlib::accusation::Accusation;
+use clap::{Arg, ArgAction, Command};
+use cluesolverlib::accusation::{Accusation, FinalAccusation, Response};
 use cluesolverlib::cards::*;
+use cluesolverlib::card_set::CardSet;
+use cluesolverlib::deck::{Deck, EDITIONS};
 use cluesolverlib::player_hand::PlayerHand;
 use cluesolverlib::solver::{propagate_state, get_potentially_winning_cards, get_guaranteed_winning_cards};
-use std::collections::HashSet;
-use std::{iter, vec};
+use cluesolverlib::probability::get_card_probabilities;
+use cluesolverlib::constraint_solver::{propagate_state_complete, is_consistent};
+use cluesolverlib::mistakes::{Entry, find_contradiction_core, get_tolerant_game_state, rank_likely_mistakes};
+use cluesolverlib::holder::Holder;
+use cluesolverlib::observation::Observation;
+use cluesolverlib::merge::{Conflict, merge_game_states};
+use cluesolverlib::advice::{get_cards_seen_of_self, get_card_to_show};
+use cluesolverlib::threats::{count_candidate_envelopes, count_opponent_candidate_envelopes_upper_bound};
+use cluesolverlib::rules::{GameRules, PlayDirection, ResponseRule};
+use cluesolverlib::audit::{Reveal, find_wrong_facts, find_impossible_accusations, find_impossible_final_accusations};
+use cluesolverlib::deduction::{Fact, get_reasoning_chain};
+use std::vec;
 use std::fmt::Display;
 use std::str::FromStr;
 use std::io;
 use colored::*;
+use error_chain::bail;
 
 use cluesolverlib::errors::*;
 
@@ -17,28 +30,115 @@ use cluesolverlib::game_state::*;
 
 const GAME_STATE_PATH: &str = "game_state.json";
 
+/// Fewest players, counting dummy hands, that a game can be played with
+const MIN_PLAYERS: usize = 2;
+
 fn main() {
     let matches = Command::new("cluesolver")
             
         .subcommand(
             Command::new("init")
-            .about("Start a new Game"))
+            .about("Start a new Game")
+            .arg(
+                Arg::new("deck")
+                .long("deck")
+                .value_name("FILE")
+                .conflicts_with("edition")
+                .help("Play with the cards listed in a deck file, instead of the classic deck"))
+            .arg(
+                Arg::new("edition")
+                .long("edition")
+                .value_name("NAME")
+                .value_parser(EDITIONS)
+                .help("Play with the cards of a built in edition of the game"))
+            .arg(
+                Arg::new("everyone-shows")
+                .long("everyone-shows")
+                .action(ArgAction::SetTrue)
+                .help("Every player who can disprove an accusation shows a card, not just the first"))
+            .arg(
+                Arg::new("counterclockwise")
+                .long("counterclockwise")
+                .action(ArgAction::SetTrue)
+                .help("Play passes to the right, so players are asked in the reverse of the order they're entered"))
+            .arg(
+                Arg::new("spectate")
+                .long("spectate")
+                .action(ArgAction::SetTrue)
+                .help("Watch the game rather than play, so you have no hand")))
         .subcommand(
             Command::new("accuse")
             .about("Add a record of accusation"))
+        .subcommand(
+            Command::new("final")
+            .about("Add a record of a wrong final accusation, which puts the player out of the game"))
+        .subcommand(
+            Command::new("observe")
+            .about("Add a record of a card seen, or known missing, outside of an accusation"))
+        .subcommand(
+            Command::new("respond")
+            .about("Advises which card to show when you have to disprove an accusation"))
+        .subcommand(
+            Command::new("merge")
+            .about("Merges in a teammate's game file, combining what both of you know")
+            .arg(
+                Arg::new("file")
+                .required(true)
+                .help("The teammate's game state file")))
+        .subcommand(
+            Command::new("end")
+            .about("Records the cards revealed at the end of the game, and checks the game against them"))
         .subcommand(
             Command::new("verify")
                 .about("Verifies Game State"))
         .subcommand(
             Command::new("wins")
-            .about("Finds potential matches for winning cards"))
+            .about("Finds potential matches for winning cards")
+            .arg(
+                Arg::new("complete")
+                .long("complete")
+                .action(ArgAction::SetTrue)
+                .help("Search every possible deal instead of applying deduction rules"))
+            .arg(
+                Arg::new("tolerate")
+                .long("tolerate")
+                .value_name("N")
+                .value_parser(clap::value_parser!(usize))
+                .conflicts_with("complete")
+                .help("Only trust conclusions that hold even if up to N accusations were entered wrong, searching every possible deal")))
+        .subcommand(
+            Command::new("odds")
+            .about("Shows the chance of each card being with each player or in the envelope"))
+        .subcommand(
+            Command::new("threats")
+            .about("Shows at most how many envelopes each opponent could still have left"))
+        .subcommand(
+            Command::new("explain")
+            .about("Explains how we know where a card is")
+            .arg(
+                Arg::new("card")
+                .help("Name of the card, asks for one if not given")))
 
         .get_matches();
 
 
     let result = match matches.subcommand() {
-        Some(("init", _sub_matches)) => {
-            new_game()
+        Some(("init", sub_matches)) => {
+            let rules = GameRules {
+                response_rule: if sub_matches.get_flag("everyone-shows") {
+                    ResponseRule::EveryoneShows
+                } else {
+                    ResponseRule::FirstCanShow
+                },
+                play_direction: if sub_matches.get_flag("counterclockwise") {
+                    PlayDirection::Counterclockwise
+                } else {
+                    PlayDirection::Clockwise
+                },
+            };
+
+            new_game(sub_matches.get_one::<String>("deck"), sub_matches.get_one::<String>("edition"), rules,
+                sub_matches.get_flag("spectate"))
         },
 
         Some(("verify", _sub_matches)) => {
@@ -48,8 +148,32 @@ fn main() {
         Some(("accuse", _sub_matches)) => {
             accuse()
         }
-        Some(("wins", _sub_matches)) => {
-            wins()
+        Some(("final", _sub_matches)) => {
+            final_accusation()
+        }
+        Some(("observe", _sub_matches)) => {
+            observe()
+        }
+        Some(("respond", _sub_matches)) => {
+            respond()
+        }
+        Some(("merge", sub_matches)) => {
+            merge(sub_matches.get_one::<String>("file").unwrap())
+        }
+        Some(("end", _sub_matches)) => {
+            end()
+        }
+        Some(("wins", sub_matches)) => {
+            wins(sub_matches.get_flag("complete"), sub_matches.get_one::<usize>("tolerate").copied())
+        }
+        Some(("odds", _sub_matches)) => {
+            odds()
+        }
+        Some(("threats", _sub_matches)) => {
+            threats()
+        }
+        Some(("explain", sub_matches)) => {
+            explain(sub_matches.get_one::<String>("card"))
         }
         _ => {
             Ok(())
@@ -65,66 +189,100 @@ fn main() {
 
 
 
-fn new_game() -> Result<()> {
-    println!("Starting a new Game!\n\n");
-
-    let user_name = 
-        get_string_from_user("Please Enter your name", |_|{true})?;
-
-    
-    let number_of_other_players: usize = 
-        get_number_from_user("\nHow many other players are there?")?;
+fn new_game(deck_path: Option<&String>, edition: Option<&String>, rules: GameRules, spectate: bool) -> Result<()> {
+    let deck = match (deck_path, edition) {
+        (Some(path), _) => Deck::read_from_file(path)?,
+        (None, Some(edition)) => Deck::from_edition(edition)?,
+        (None, None) => Deck::classic(),
+    };
 
-    let mut self_hand: PlayerHand = 
-        PlayerHand::new(user_name.trim().to_owned());
+    println!("Starting a new Game with the {} deck!\n\n", deck.name);
 
-    let number_of_public_cards = 18 % (number_of_other_players + 1);
+    let user_name = if spectate {
+        None
+    } else {
+        Some(get_string_from_user("Please Enter your name", |_|{true})?)
+    };
 
-    let number_of_cards_per_player = 
-        (18 - number_of_public_cards) / (number_of_other_players + 1);
+    let number_of_other_players: usize = if spectate {
+        get_number_from_user("\nHow many players are there, counting any dummy hands?")?
+    } else {
+        get_number_from_user("\nHow many other players are there, counting any dummy hands?")?
+    };
 
-    println!("\nPlease enter the cards you have:\n");
-    
-    for _ in 0..number_of_cards_per_player {
-        let user_card = get_card_from_user()?;
+    let number_of_players = number_of_other_players + usize::from(!spectate);
 
-        self_hand.must_have.insert(user_card);
+    if number_of_players < MIN_PLAYERS {
+        bail!(ErrorKind::TooFewPlayers(number_of_players, MIN_PLAYERS));
     }
 
-    println!("Completed with Self Setup...\n");
-
     let mut other_player_hands: Vec<PlayerHand> = Vec::new();
 
     for _ in 0..number_of_other_players {
         let other_player_name = 
             get_string_from_user("\n\nPlease Enter the name of the next player:", |_|{true})?;
 
-        other_player_hands.push(PlayerHand::new(other_player_name))
+        if get_yes_no_from_user("Is this a dummy hand, which responds but never takes a turn? (y/n)")? {
+            other_player_hands.push(PlayerHand::new_dummy(other_player_name))
+        } else {
+            other_player_hands.push(PlayerHand::new(other_player_name))
+        }
+    }
+
+    let mut all_players: Vec<PlayerHand> = 
+        user_name.map(|user_name| PlayerHand::new(user_name.trim().to_owned()))
+        .into_iter()
+        .chain(other_player_hands)
+        .collect();
+
+    let hand_sizes = get_hand_sizes_from_user(&deck, &all_players, rules.play_direction)?;
+
+    for (player, hand_size) in all_players.iter_mut().zip(hand_sizes.iter()) {
+        player.hand_size = Some(*hand_size);
+    }
+
+    if !spectate {
+        println!("\nPlease enter the cards you have:\n");
+
+        for _ in 0..hand_sizes[0] {
+            let user_card = get_card_from_user(&deck)?;
+
+            all_players[0].must_have.insert(user_card);
+        }
+
+        println!("Completed with Self Setup...\n");
     }
 
     println!("Who is starting the game?");
 
-    let all_players = 
-        iter::once(self_hand.clone())
-        .chain(other_player_hands.clone())
+    let dummy_hands: Vec<usize> = (0..all_players.len())
+        .filter(|player_idx| all_players[*player_idx].is_dummy())
         .collect();
 
+    let starting_player = get_player_from_user(&all_players, dummy_hands)?;
 
-    let starting_player = get_player_from_user(&all_players, vec![])?;
+    let number_of_public_cards = deck.get_number_of_cards_outside_envelope() - hand_sizes.iter().sum::<usize>();
 
-    let mut public_cards = HashSet::new();
+    let mut public_cards = CardSet::new();
 
     for public_card_index in 0..number_of_public_cards {
         println!("\n\nPublic Facing Card #{}", public_card_index);
 
-        let card = get_card_from_user()?;
+        let card = get_card_from_user(&deck)?;
 
         public_cards.insert(card);        
     }
 
+    let gs = if spectate {
+        all_players.rotate_left(starting_player);
 
+        GameState::new_spectated_game_state(deck, all_players, public_cards, rules)
+    } else {
+        let other_player_hands = all_players.split_off(1);
+        let self_hand = all_players.remove(0);
 
-    let gs = GameState::new_game_state(self_hand, other_player_hands, starting_player, public_cards);
+        GameState::new_game_state(deck, self_hand, other_player_hands, starting_player, public_cards, rules)
+    };
     
     gs.verify_state()?;
 
@@ -135,77 +293,277 @@ fn new_game() -> Result<()> {
 
 }
 
+/// Asks how the cards were dealt, giving the number of cards each player
+/// has in seat order.
+fn get_hand_sizes_from_user(deck: &Deck, players: &[PlayerHand], play_direction: PlayDirection) -> Result<Vec<usize>> {
+    let number_of_players = players.len();
+    let number_of_cards = deck.get_number_of_cards_outside_envelope();
+
+    let even_hand_size = number_of_cards / number_of_players;
+
+    if number_of_cards.is_multiple_of(number_of_players) {
+        return Ok(vec![even_hand_size; number_of_players]);
+    }
+
+    println!("\nThe cards don't split evenly, how were they dealt?");
+
+    let deal_options = [
+        "Evenly, with the rest face up",
+        "Every card, starting after the dealer",
+        "Let me enter each hand size",
+    ];
+
+    match get_list_index_from_user(&mut deal_options.iter().enumerate())? {
+        0 => Ok(vec![even_hand_size; number_of_players]),
+        1 => {
+            println!("\nWho dealt the cards?");
+            let dealer_index = get_player_from_user(players, vec![])?;
+
+            Ok(deck.get_dealt_hand_sizes(number_of_players, dealer_index, play_direction))
+        },
+        _ => loop {
+            let mut hand_sizes: Vec<usize> = Vec::new();
+
+            for player in players {
+                hand_sizes.push(get_number_from_user(&format!("\nHow many cards does {} have?", player.player_name))?);
+            }
+
+            let number_of_dealt_cards: usize = hand_sizes.iter().sum();
+
+            if number_of_dealt_cards <= number_of_cards {
+                return Ok(hand_sizes);
+            }
+
+            println!("That's {} cards, while there are only {} to deal. Please try again.", number_of_dealt_cards, number_of_cards);
+        },
+    }
+}
+
 fn accuse() -> Result<()> {
     let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
     
     println!("Who is making the Accusation?");
-    let accuser_player_index = get_player_from_user(&gs.player_hands, vec![])?;
+    let accuser_player_index = get_player_from_user(&gs.player_hands, gs.get_players_without_turns())?;
 
     println!("\n{}'s Turn Now!", gs.player_hands[accuser_player_index].player_name.purple());
 
 
     println!("\n\nPlease enter their accusation");
-    let room = get_room_card_from_user()?;
-    println!();
-    let weapon = get_weapon_card_from_user()?;
-    println!();
-    let suspect = get_suspect_card_from_user()?;
+    let mut accused_cards: Vec<Card> = Vec::new();
 
+    for category_index in 0..gs.deck.get_number_of_categories() {
+        println!();
+        accused_cards.push(get_card_in_category_from_user(&gs.deck, category_index)?);
+    }
 
 
-    let someone_respond = get_yes_no_from_user("\nDid anyone respond? (y/n)")?;
+    let asked_players = get_asked_players_from_user(&gs, accuser_player_index)?;
 
-    let responding_player_index: Option<usize>;
-    let card_shown: Option<Card>;
+    // Only players who were asked can respond
+    let not_asked: Vec<usize> = match &asked_players {
+        Some(asked_players) => (0..gs.player_hands.len())
+            .filter(|player_idx| !asked_players.contains(player_idx))
+            .collect(),
+        None => vec![accuser_player_index],
+    };
 
-    let is_accuser_self = accuser_player_index == gs.self_index;
+    let number_who_can_respond = gs.player_hands.len() - not_asked.len();
 
-    if !someone_respond {
-        responding_player_index = None;
-        card_shown = None;
-    } else {
-        println!("\nWho Responded?");
-        responding_player_index = Some(get_player_from_user(&gs.player_hands, vec![accuser_player_index])?);
+    let mut responding_player_index: Option<usize> = None;
+    let mut card_shown: Option<Card> = None;
+    let mut responses: Vec<Response> = Vec::new();
+    let mut possible_responders: Vec<usize> = Vec::new();
 
-        let potential_cards = 
-                vec![Card::RoomCard(room.clone()),
-                Card::WeaponCard(weapon.clone()),
-                Card::SuspectCard(suspect.clone())];
+    match gs.rules.response_rule {
+        ResponseRule::FirstCanShow => {
+            if number_who_can_respond > 0 && get_yes_no_from_user("\nDid anyone respond? (y/n)")? {
+                let is_accuser_self = gs.is_self(accuser_player_index);
 
-        if is_accuser_self {
-            println!("\n\nWhat card did they show you?");
+                if is_accuser_self || get_yes_no_from_user("\nDid you see who responded? (y/n)")? {
+                    println!("\nWho Responded?");
+                    let responder = get_player_from_user(&gs.player_hands, not_asked)?;
 
-            card_shown = Some(get_list_item_from_user(&mut potential_cards.iter())?.clone());
+                    responding_player_index = Some(responder);
+                    card_shown = get_card_shown_from_user(&gs, accuser_player_index, responder, &accused_cards)?;
+                } else {
+                    possible_responders = get_possible_responders_from_user(&gs, not_asked)?;
 
-        } else {
+                    // Narrowed down to one, so we know who it was
+                    if possible_responders.len() == 1 {
+                        responding_player_index = possible_responders.pop();
+                    }
+                }
+            }
+        },
+        ResponseRule::EveryoneShows => {
+            let mut prompt = "\nDid anyone respond? (y/n)";
 
-            let is_responder_self = responding_player_index.unwrap() ==  gs.self_index;
+            while responses.len() < number_who_can_respond && get_yes_no_from_user(prompt)? {
+                let mut cannot_respond = not_asked.clone();
+                cannot_respond.extend(responses.iter().map(|response| response.player_index));
 
-            if is_responder_self {
-                println!("\n\nWhat card did you show them?");
+                println!("\nWho Responded?");
+                let responder = get_player_from_user(&gs.player_hands, cannot_respond)?;
 
-                card_shown = Some(get_list_item_from_user(&mut potential_cards.iter())?.clone());
+                responses.push(Response {
+                    player_index: responder,
+                    card_shown: get_card_shown_from_user(&gs, accuser_player_index, responder, &accused_cards)?,
+                });
 
-            }else {
-                // Card shown secretly, no info
-                card_shown = None;
+                prompt = "\nDid anyone else respond? (y/n)";
             }
-        }
+        },
     }
 
 
     gs.add_accusation(Accusation {
         accuser_player_index,
-        room,
-        suspect,
-        weapon,
+        accused_cards,
         responding_player_index,
         card_shown,
+        responses,
+        asked_players,
+        possible_responders,
     });
 
     propagate_state(&mut gs)?;
 
-    gs.verify_state()?;
+    if let Err(e) = gs.verify_state() {
+        report_contradiction(&gs);
+        return Err(e);
+    }
+
+    gs.save_to_file(GAME_STATE_PATH)?;
+
+    println!("{} {}", "Game State Verified Successfully!".green(), "Saved to File.".purple());
+
+    Ok(())
+}
+
+/// The players asked to respond, in order, if it wasn't everyone in the direction of play
+fn get_asked_players_from_user(gs: &GameState, accuser_player_index: usize) -> Result<Option<Vec<usize>>> {
+    if get_yes_no_from_user("\nWas everyone asked in turn? (y/n)")? {
+        return Ok(None);
+    }
+
+    let mut asked_players: Vec<usize> = Vec::new();
+    let mut prompt = "\nWas anyone asked? (y/n)";
+
+    while asked_players.len() + 1 < gs.player_hands.len() && get_yes_no_from_user(prompt)? {
+        let mut cannot_be_asked = vec![accuser_player_index];
+        cannot_be_asked.extend(asked_players.iter());
+
+        println!("\nWho was asked next?");
+        asked_players.push(get_player_from_user(&gs.player_hands, cannot_be_asked)?);
+
+        prompt = "\nWas anyone else asked? (y/n)";
+    }
+
+    Ok(Some(asked_players))
+}
+
+/// Players who might have been the one to respond
+fn get_possible_responders_from_user(gs: &GameState, not_asked: Vec<usize>) -> Result<Vec<usize>> {
+    let mut possible_responders: Vec<usize> = Vec::new();
+    let number_who_can_respond = gs.player_hands.len() - not_asked.len();
+
+    loop {
+        let mut cannot_respond = not_asked.clone();
+        cannot_respond.extend(possible_responders.iter());
+
+        println!("\nWho might have responded?");
+        possible_responders.push(get_player_from_user(&gs.player_hands, cannot_respond)?);
+
+        if possible_responders.len() == number_who_can_respond
+            || !get_yes_no_from_user("\nMight anyone else have responded? (y/n)")? {
+            return Ok(possible_responders);
+        }
+    }
+}
+
+/// The card the responder showed, if we were the accuser or the responder
+fn get_card_shown_from_user(gs: &GameState, accuser_player_index: usize, responding_player_index: usize,
+    accused_cards: &[Card]) -> Result<Option<Card>> {
+
+    if gs.is_self(accuser_player_index) {
+        println!("\n\nWhat card did {} show you?", gs.player_hands[responding_player_index].player_name);
+
+        Ok(Some(get_one_of_cards_from_user(&gs.deck, accused_cards)?))
+
+    } else if gs.is_self(responding_player_index) {
+        println!("\n\nWhat card did you show them?");
+
+        Ok(Some(get_one_of_cards_from_user(&gs.deck, accused_cards)?))
+
+    } else {
+        // Card shown secretly, no info
+        Ok(None)
+    }
+}
+
+fn final_accusation() -> Result<()> {
+    let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
+
+    println!("Who made the wrong final accusation?");
+    let accuser_player_index = get_player_from_user(&gs.player_hands, gs.get_players_without_turns())?;
+
+    println!("\n\nPlease enter their final accusation");
+    let mut accused_cards: Vec<Card> = Vec::new();
+
+    for category_index in 0..gs.deck.get_number_of_categories() {
+        println!();
+        accused_cards.push(get_card_in_category_from_user(&gs.deck, category_index)?);
+    }
+
+    gs.add_final_accusation(FinalAccusation {
+        accuser_player_index,
+        accused_cards,
+    });
+
+    propagate_state(&mut gs)?;
+
+    if let Err(e) = gs.verify_state() {
+        report_contradiction(&gs);
+        return Err(e);
+    }
+
+    gs.save_to_file(GAME_STATE_PATH)?;
+
+    println!("\n{} is out of the game, but still responds to accusations.", 
+        gs.player_hands[accuser_player_index].player_name.purple());
+    println!("{} {}", "Game State Verified Successfully!".green(), "Saved to File.".purple());
+
+    Ok(())
+}
+
+fn observe() -> Result<()> {
+    let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
+
+    loop {
+        println!("Whose hand is it about?");
+        let player_index = get_player_from_user(&gs.player_hands, gs.self_index.into_iter().collect())?;
+
+        println!();
+        let card = get_card_from_user(&gs.deck)?;
+
+        let has = get_yes_no_from_user(&format!("\nDoes {} have {}? (y/n)",
+            gs.player_hands[player_index].player_name, gs.get_card_name(&card)))?;
+
+        gs.add_observation(Observation { player_index, card, has });
+
+        if !get_yes_no_from_user("\nWas anything else seen? (y/n)")? {
+            break;
+        }
+
+        println!();
+    }
+
+    propagate_state(&mut gs)?;
+
+    if let Err(e) = gs.verify_state() {
+        report_contradiction(&gs);
+        return Err(e);
+    }
 
     gs.save_to_file(GAME_STATE_PATH)?;
 
@@ -214,142 +572,534 @@ fn accuse() -> Result<()> {
     Ok(())
 }
 
+fn respond() -> Result<()> {
+    let gs = GameState::read_from_file(GAME_STATE_PATH)?;
+
+    let self_index = match gs.self_index {
+        Some(self_index) => self_index,
+        None => bail!(ErrorKind::SpectatorHasNoHand),
+    };
+
+    println!("What each player has seen of your hand:");
+
+    for (player_idx, cards_seen) in get_cards_seen_of_self(&gs).iter().enumerate() {
+        if player_idx == self_index {
+            continue;
+        }
+
+        let card_names: Vec<String> = cards_seen.iter().map(|card| gs.get_card_name(&card)).collect();
+
+        if card_names.is_empty() {
+            println!("{:<18}{}", gs.player_hands[player_idx].player_name, "nothing".dimmed());
+        } else {
+            println!("{:<18}{}", gs.player_hands[player_idx].player_name, card_names.join(", "));
+        }
+    }
+
+    let mut cannot_accuse = gs.get_players_without_turns();
+    cannot_accuse.push(self_index);
+
+    println!("\nWho is making the Accusation?");
+    let accuser_player_index = get_player_from_user(&gs.player_hands, cannot_accuse)?;
+
+    println!("\nPlease enter their accusation");
+    let mut accused_cards: Vec<Card> = Vec::new();
+
+    for category_index in 0..gs.deck.get_number_of_categories() {
+        println!();
+        accused_cards.push(get_card_in_category_from_user(&gs.deck, category_index)?);
+    }
+
+    match get_card_to_show(&gs, accuser_player_index, &accused_cards) {
+        Some(card) => println!("\nShow them {}", gs.get_card_name(&card).green()),
+        None => println!("\n{}", "You have none of those cards, so can't disprove it".yellow()),
+    }
+
+    Ok(())
+}
+
+fn merge(path: &str) -> Result<()> {
+    let ours = GameState::read_from_file(GAME_STATE_PATH)?;
+    let theirs = GameState::read_from_file(path)?;
+
+    let (gs, conflicts) = merge_game_states(&ours, &theirs)?;
+
+    if !conflicts.is_empty() {
+        println!("{}", "These disagree between the two games, so yours were kept:".yellow());
+
+        for conflict in conflicts {
+            match conflict {
+                Conflict::Accusation(our_turn, their_turn) | Conflict::Response(our_turn, their_turn) =>
+                    println!("  Turn {}: {} vs {}", our_turn + 1,
+                        describe_accusation(&ours, &ours.accusations[our_turn]),
+                        describe_accusation(&theirs, &theirs.accusations[their_turn])),
+                Conflict::GivenFact(fact) =>
+                    println!("  {} vs {}", describe_fact(&ours, &Fact::new(fact.holder, fact.card, !fact.has)),
+                        describe_fact(&theirs, &fact)),
+                Conflict::HandSize(player_idx) => println!("  {} has {} cards vs {}",
+                    ours.player_hands[player_idx].player_name,
+                    ours.get_hand_size(player_idx), theirs.get_hand_size(player_idx)),
+            }
+        }
+
+        println!();
+    }
+
+    if let Err(e) = gs.verify_state() {
+        report_contradiction(&gs);
+        return Err(e);
+    }
+
+    gs.save_to_file(GAME_STATE_PATH)?;
+
+    println!("{} {}", "Game State Verified Successfully!".green(), "Saved to File.".purple());
+
+    Ok(())
+}
+
+fn end() -> Result<()> {
+    let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
+
+    println!("Please enter the correct accusation");
+    let mut envelope = CardSet::new();
+
+    for category_index in 0..gs.deck.get_number_of_categories() {
+        println!();
+        envelope.insert(get_card_in_category_from_user(&gs.deck, category_index)?);
+    }
+
+    let mut player_hands: Option<Vec<CardSet>> = None;
+
+    if get_yes_no_from_user("\nDid everyone show their hand? (y/n)")? {
+        let mut hands: Vec<CardSet> = Vec::new();
+
+        // Cards can't be in two places
+        let mut placed_cards: CardSet = &gs.public_cards | &envelope;
+
+        if let Some(self_index) = gs.self_index {
+            placed_cards |= &gs.player_hands[self_index].must_have;
+        }
+
+        for (player_idx, player) in gs.player_hands.iter().enumerate() {
+            if gs.is_self(player_idx) {
+                hands.push(player.must_have);
+                continue;
+            }
+
+            println!("\n\nPlease enter the cards {} had:\n", player.player_name.purple());
+
+            let mut hand = CardSet::new();
+            while hand.len() < gs.get_hand_size(player_idx) {
+                let card = get_card_from_user(&gs.deck)?;
+
+                if placed_cards.contains(&card) {
+                    println!("{}", "That card was face up, in the envelope or in another hand, please enter another".red());
+                    continue;
+                }
+
+                hand.insert(card);
+                placed_cards.insert(card);
+            }
+
+            hands.push(hand);
+        }
+
+        player_hands = Some(hands);
+    }
+
+    let reveal = Reveal { envelope, player_hands };
+    reveal.verify(&gs)?;
+
+    let wrong_facts = find_wrong_facts(&gs, &reveal);
+    let impossible_accusations = find_impossible_accusations(&gs, &reveal);
+    let impossible_final_accusations = find_impossible_final_accusations(&gs, &reveal);
+
+    gs.reveal = Some(reveal);
+    gs.save_to_file(GAME_STATE_PATH)?;
+
+    if wrong_facts.is_empty() && impossible_accusations.is_empty() && impossible_final_accusations.is_empty() {
+        println!("\n{}", "Everything in the game matches the cards revealed!".green());
+    }
+
+    if !wrong_facts.is_empty() {
+        println!("\n{}", "These were believed, yet turned out wrong:".yellow());
+
+        for fact in wrong_facts.iter() {
+            match gs.get_deduction(fact) {
+                Some(deduction) => println!("  {} ({})", describe_fact(&gs, fact), deduction.rule),
+                None => println!("  {} (entered)", describe_fact(&gs, fact)),
+            }
+        }
+    }
+
+    if !impossible_accusations.is_empty() {
+        println!("\n{}", "These accusations couldn't have happened as entered:".yellow());
+
+        for acc_idx in impossible_accusations {
+            println!("  Turn {}: {}", acc_idx + 1, describe_accusation(&gs, &gs.accusations[acc_idx]));
+        }
+    }
+
+    if !impossible_final_accusations.is_empty() {
+        println!("\n{}", "These final accusations were entered as wrong, yet named the envelope's cards:".yellow());
+
+        for final_idx in impossible_final_accusations {
+            let final_accusation = &gs.final_accusations[final_idx];
+            let card_names: Vec<String> = final_accusation.accused_cards.iter().map(|card| gs.get_card_name(card)).collect();
+
+            println!("  {} accused {}", gs.player_hands[final_accusation.accuser_player_index].player_name, card_names.join(", "));
+        }
+    }
+
+    println!("{}", "Saved to File.".purple());
+
+    Ok(())
+}
+
 fn verify() -> Result<()> {
     let gs = GameState::read_from_file(GAME_STATE_PATH)?;
 
-    gs.verify_state()?;
+    if let Err(e) = gs.verify_state() {
+        report_contradiction(&gs);
+        return Err(e);
+    }
+
+    // The rules can miss contradictions, so check every deal too
+    if !is_consistent(&gs) {
+        report_contradiction(&gs);
+        bail!(ErrorKind::NoConsistentDeals);
+    }
 
     println!("{}", "Game State Verified!".green());
 
     Ok(())
 }
 
-fn wins() -> Result<()> {
+fn wins(complete: bool, tolerance: Option<usize>) -> Result<()> {
     let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
     
-    propagate_state(&mut gs)?;
-    
-    gs.verify_state()?;
+    if let Some(tolerance) = tolerance {
+        print_likely_mistakes(&gs, tolerance)?;
+
+        // The accusations themselves may be wrong, so they aren't verified
+        gs = get_tolerant_game_state(&gs, tolerance)?;
+    } else {
+        if complete {
+            propagate_state_complete(&mut gs)?;
+        } else {
+            propagate_state(&mut gs)?;
+        }
+
+        gs.verify_state()?;
+    }
 
 
     let guaranteed_wins = get_guaranteed_winning_cards(&gs);
     let mut potential_wins = get_potentially_winning_cards(&gs);
 
-    // Removes any cards that are same type as guaranteed wins
-    potential_wins.retain(|card| {
-        for gcard in guaranteed_wins.iter() {
-            if gcard.variant_eq(card) {
-                return false;
-            }
-        }
-        true
-    });
+    // Removes any cards that are same category as guaranteed wins
+    for card in guaranteed_wins.iter() {
+        potential_wins = &potential_wins - &gs.deck.get_all_cards_in_category(gs.deck.get_category_index(&card));
+    }
 
     
     println!("Guaranteed Win Cards:");
     for card in guaranteed_wins.iter() {
-        println!("{}", card);
+        println!("{}", gs.get_card_name(&card));
     }
 
 
     println!("\nPotential Win Cards:");
     
+    for (category_index, category) in gs.deck.categories.iter().enumerate() {
+        print!("{}: ", category.name);
 
-    print!("Rooms: ");
-    potential_wins.iter().for_each(|card| {
-        // Only get Rooms
-        if !card.variant_eq(&Card::RoomCard(Room::Hall)) {
-            return;
-        }
-        print!("{}  ", card);
-    });
+        let category_wins = &potential_wins & &gs.deck.get_all_cards_in_category(category_index);
+        category_wins.iter().for_each(|card| {
+            print!("{}  ", gs.get_card_name(&card));
+        });
+
+        println!();
+    }
+
+    Ok(())
+}
+
+fn odds() -> Result<()> {
+    let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
 
-    print!("\nWeapons: ");
-    potential_wins.iter().for_each(|card| {
-        // Only get Weapons
-        if !card.variant_eq(&Card::WeaponCard(Weapon::Pistol)) {
-            return;
+    propagate_state(&mut gs)?;
+
+    gs.verify_state()?;
+
+    let probabilities = get_card_probabilities(&gs)?;
+
+    println!("Out of {} possible deals:", probabilities.total_deals);
+
+    for (category_index, category) in gs.deck.categories.iter().enumerate() {
+        print!("\n{:<18}", category.name.bold());
+        for holder in probabilities.holders.iter() {
+            print!("{:>12}", gs.get_holder_name(*holder));
         }
-        print!("{}  ", card);
-    });
+        println!();
+
+        for card in &gs.deck.get_all_cards_in_category(category_index) - &gs.public_cards {
+            let row = probabilities.holders.iter()
+                .map(|holder| format!("{:>11.1}%", 100.0 * probabilities.get_probability(&card, *holder)))
+                .collect::<String>();
+
+            let line = format!("{:<18}{}", gs.get_card_name(&card), row);
+            let envelope_probability = probabilities.get_probability(&card, Holder::Envelope);
+
+            if envelope_probability == 1.0 {
+                println!("{}", line.green());
+            } else if envelope_probability == 0.0 {
+                println!("{}", line.dimmed());
+            } else {
+                println!("{}", line);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn threats() -> Result<()> {
+    let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
+
+    propagate_state(&mut gs)?;
+
+    gs.verify_state()?;
 
+    let players_without_turns = gs.get_players_without_turns();
 
-    print!("\nSuspects: ");
-    potential_wins.iter().for_each(|card| {
-        // Only get Rooms
-        if !card.variant_eq(&Card::SuspectCard(Suspect::Green)) {
-            return;
+    // Their own hands are unknown to us, so they could have ruled out more
+    println!("Most envelopes each player could still have left, from what they've seen:");
+
+    if gs.self_index.is_some() {
+        println!("{:<18}{:>10}", "you".bold(), count_candidate_envelopes(&gs));
+    }
+
+    for player_idx in 0..gs.player_hands.len() {
+        if gs.is_self(player_idx) || players_without_turns.contains(&player_idx) {
+            continue;
         }
-        print!("{}  ", card);
-    });
 
-    println!();
+        let candidate_envelopes = count_opponent_candidate_envelopes_upper_bound(&gs, player_idx)?;
+        let line = format!("{:<18}{:>10}", gs.player_hands[player_idx].player_name, candidate_envelopes);
+
+        if candidate_envelopes == 1 {
+            println!("{}", line.red());
+        } else {
+            println!("{}", line);
+        }
+    }
 
     Ok(())
 }
 
+fn explain(card_name: Option<&String>) -> Result<()> {
+    let mut gs = GameState::read_from_file(GAME_STATE_PATH)?;
 
-// -------------------------------
-// ------User Input Helpers-------
-// -------------------------------
+    propagate_state(&mut gs)?;
 
-pub fn get_card_from_user() -> Result<Card> {
+    gs.verify_state()?;
 
-    let card_type = get_string_from_user(
-        "r) Room\nw) Weapon\ns) Suspect", 
-        |user_input| {
-            if user_input.chars().next().is_none() {
-                return false;
+    let card = match card_name {
+        Some(name) => match gs.deck.find_card(name) {
+            Some(card) => card,
+            None => {
+                println!("No card named \"{}\"", name);
+                return Ok(());
             }
+        },
+        None => get_card_from_user(&gs.deck)?,
+    };
+
+    if gs.public_cards.contains(&card) {
+        println!("{} is face up, so it isn't in anyone's hand or the envelope.", gs.get_card_name(&card).bold());
+        return Ok(());
+    }
+
+    let holders = gs.get_holders();
+
+    // Explain who has it, or failing that, why it isn't in the envelope
+    let facts_to_explain: Vec<Fact> = match holders.iter().find(|holder| gs.get_hand(**holder).must_have.contains(&card)) {
+        Some(holder) => vec![Fact::new(*holder, card, true)],
+        None => holders.iter()
+            .map(|holder| Fact::new(*holder, card, false))
+            .filter(|fact| gs.is_known(fact))
+            .collect(),
+    };
 
-            if user_input.starts_with('r') || 
-                user_input.starts_with('w') || 
-                user_input.starts_with('s') {
-                    return true; 
+    if facts_to_explain.is_empty() {
+        println!("Nothing is known yet about where {} is.", gs.get_card_name(&card).bold());
+        return Ok(());
+    }
+
+    let mut steps: Vec<Fact> = Vec::new();
+    for fact in facts_to_explain.iter() {
+        for step in get_reasoning_chain(&gs, fact) {
+            if !steps.contains(&step) {
+                steps.push(step);
             }
+        }
+    }
 
-            false
-        })?;
+    for (step_idx, fact) in steps.iter().enumerate() {
+        let reason = match gs.get_deduction(fact) {
+            None if matches!(fact.holder, Holder::Player(player_idx) if gs.is_self(player_idx)) => "your own hand, known from the start".to_owned(),
+            None => "recorded without a reason".to_owned(),
+            Some(deduction) => {
+                let mut reason = deduction.rule.to_string();
 
+                if let Some(acc_idx) = deduction.accusation_index {
+                    reason += &format!(" on turn {} ({})", acc_idx + 1, describe_accusation(&gs, &gs.accusations[acc_idx]));
+                }
 
-    loop {
-        if card_type.starts_with('r') {
-            // Room Card
-            let selected_room_card = get_room_card_from_user()?;
+                let premise_steps: Vec<String> = deduction.premises.iter()
+                    .filter_map(|premise| steps.iter().position(|step| step == premise))
+                    .map(|premise_idx| (premise_idx + 1).to_string())
+                    .collect();
 
-            return Ok(Card::RoomCard(selected_room_card));
-        
-        } else if card_type.starts_with('w') {
-            // Weapon Card
-            let selected_room_card = get_weapon_card_from_user()?;
+                if !premise_steps.is_empty() {
+                    reason += &format!(", from {}", premise_steps.join(", "));
+                }
 
-            return Ok(Card::WeaponCard(selected_room_card));
+                reason
+            }
+        };
 
-        } else if card_type.starts_with('s') {
-            // Suspect Card
-            let selected_room_card = get_suspect_card_from_user()?;
+        let line = format!("{:>3}. {}", step_idx + 1, describe_fact(&gs, fact));
 
-            return Ok(Card::SuspectCard(selected_room_card));
+        if facts_to_explain.contains(fact) {
+            println!("{}: {}", line.bold(), reason);
+        } else {
+            println!("{}: {}", line, reason.dimmed());
         }
     }
+
+    Ok(())
+}
+
+/// Shows the entries most likely to have been entered wrong, up to tolerance at once
+fn print_likely_mistakes(gs: &GameState, tolerance: usize) -> Result<()> {
+    let ranked = rank_likely_mistakes(gs, tolerance)?;
+
+    println!("Most likely mistakes:");
+
+    for (entries, deals) in ranked.into_iter().take(3) {
+        let entry_descriptions: Vec<String> = entries.iter().map(|entry| describe_entry(gs, entry)).collect();
+
+        println!("  {} ({} deals without it)", entry_descriptions.join(", and "), deals);
+    }
+
+    println!();
+
+    Ok(())
 }
 
-pub fn get_room_card_from_user() -> Result<Room> {
-    let selected_room_card = get_list_item_from_user(&mut enum_iterator::all::<Room>())?;
+/// If the game contradicts itself, shows the entries causing it
+fn report_contradiction(gs: &GameState) {
+    let core = match find_contradiction_core(gs) {
+        Some(core) => core,
+        None => return,
+    };
+
+    println!("\n{}", "These entries can't all be right, and each is needed for that, so one of them is likely a mistake:".yellow());
 
-    Ok(selected_room_card)
+    for entry in core {
+        println!("  {}", describe_entry(gs, &entry));
+    }
 }
 
-pub fn get_weapon_card_from_user() -> Result<Weapon> {
-    let selected_room_card = get_list_item_from_user(&mut enum_iterator::all::<Weapon>())?;
+fn describe_entry(gs: &GameState, entry: &Entry) -> String {
+    match entry {
+        Entry::Accusation(acc_idx) => 
+            format!("Turn {}: {}", acc_idx + 1, describe_accusation(gs, &gs.accusations[*acc_idx])),
+        Entry::GivenFact(fact) => 
+            describe_fact(gs, fact),
+        Entry::Observation(obs_idx) =>
+            format!("Seen: {}", describe_fact(gs, &gs.observations[*obs_idx].get_fact())),
+        Entry::FinalAccusation(final_idx) => {
+            let final_accusation = &gs.final_accusations[*final_idx];
+            let card_names: Vec<String> = final_accusation.accused_cards.iter().map(|card| gs.get_card_name(card)).collect();
+
+            format!("Wrong final accusation: {} accused {}",
+                gs.player_hands[final_accusation.accuser_player_index].player_name, card_names.join(", "))
+        }
+    }
+}
 
-    Ok(selected_room_card)
+fn describe_fact(gs: &GameState, fact: &Fact) -> String {
+    format!("{} {} {}",
+        gs.get_holder_name(fact.holder),
+        if fact.has { "has" } else { "does not have" },
+        gs.get_card_name(&fact.card))
 }
 
-pub fn get_suspect_card_from_user() -> Result<Suspect> {
-    let selected_room_card = get_list_item_from_user(&mut enum_iterator::all::<Suspect>())?;
+fn describe_accusation(gs: &GameState, acc: &Accusation) -> String {
+    let accused_card_names: Vec<String> = acc.accused_cards.iter().map(|card| gs.get_card_name(card)).collect();
+
+    let accusation = format!("{} accused {}",
+        gs.player_hands[acc.accuser_player_index].player_name,
+        accused_card_names.join(", "));
+
+    let responses: Vec<String> = gs.get_responses(acc).iter()
+        .map(|response| match &response.card_shown {
+            Some(card) => format!("{} showed {}",
+                gs.player_hands[response.player_index].player_name, gs.get_card_name(card)),
+            None => format!("{} showed a card", gs.player_hands[response.player_index].player_name),
+        })
+        .collect();
+
+    let possible_responder_names: Vec<String> = acc.possible_responders.iter()
+        .map(|player_idx| gs.player_hands[*player_idx].player_name.clone())
+        .collect();
+
+    let response = if !possible_responder_names.is_empty() {
+        format!("one of {} showed a card", possible_responder_names.join(", "))
+    } else if responses.is_empty() {
+        "nobody could respond".to_owned()
+    } else {
+        responses.join(", ")
+    };
+
+    format!("{}, {}", accusation, response)
+}
+
+// -------------------------------
+// ------User Input Helpers-------
+// -------------------------------
+
+pub fn get_card_from_user(deck: &Deck) -> Result<Card> {
+    println!("What kind of card?");
+
+    let mut category_names = deck.categories.iter()
+        .map(|category| category.name.clone())
+        .enumerate();
+
+    let category_index = get_list_index_from_user(&mut category_names)?;
+
+    println!();
+
+    get_card_in_category_from_user(deck, category_index)
+}
+
+pub fn get_card_in_category_from_user(deck: &Deck, category_index: usize) -> Result<Card> {
+    let category_cards: Vec<Card> = deck.get_all_cards_in_category(category_index).into_iter().collect();
+
+    get_one_of_cards_from_user(deck, &category_cards)
+}
+
+pub fn get_one_of_cards_from_user(deck: &Deck, cards: &[Card]) -> Result<Card> {
+    let mut card_names = cards.iter()
+        .map(|card| deck.get_card_name(card).to_owned())
+        .enumerate();
+
+    let selected_card_index = get_list_index_from_user(&mut card_names)?;
 
-    Ok(selected_room_card)
+    Ok(cards[selected_card_index])
 }
 
 pub fn get_string_from_user<F>(prompt: &str, valid_input: F) -> Result<String> where F: Fn(&str) -> bool {
@@ -388,7 +1138,7 @@ pub fn get_number_from_user<T: num::Integer + FromStr>(prompt: &str) -> Result<T
         }
 }
 
-pub fn get_player_from_user(players_hands: &Vec<PlayerHand>, exclude_indices: Vec<usize>) -> Result<usize> {
+pub fn get_player_from_user(players_hands: &[PlayerHand], exclude_indices: Vec<usize>) -> Result<usize> {
     let mut all_player_iter = 
         players_hands.iter()
             .enumerate()
@@ -409,7 +1159,7 @@ pub fn get_yes_no_from_user(prompt: &str) -> Result<bool> {
     let user_response = get_string_from_user(prompt, |user_input| {
         let cleaned_str = user_input.to_lowercase();
 
-        return cleaned_str.trim().starts_with('y') || cleaned_str.trim().starts_with('n');
+        cleaned_str.trim().starts_with('y') || cleaned_str.trim().starts_with('n')
     })?;
 
     if user_response.trim().to_lowercase().starts_with('y') {
@@ -1,15 +1,106 @@
 use crate::cards::*;
+use crate::deck::get_legacy_card;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(try_from = "SavedAccusation")]
 pub struct Accusation {
     pub accuser_player_index: usize,
-    
-    pub room: Room,
-    pub suspect: Suspect,
-    pub weapon: Weapon,
+
+    /// One card of each category, in the deck's category order
+    pub accused_cards: Vec<Card>,
 
     pub responding_player_index: Option<usize>,
 
     /// Only if the user has seen the card.
     pub card_shown: Option<Card>,
+
+    /// Every player who showed a card, when everyone who can shows one.
+    /// Otherwise empty, and the responding player is the one who showed.
+    pub responses: Vec<Response>,
+
+    /// Players who were asked to respond, in order, when it wasn't
+    /// everyone in the direction of play. None if it was.
+    pub asked_players: Option<Vec<usize>>,
+
+    /// Players who might have responded, when someone showed a card but
+    /// we missed who. Otherwise empty.
+    pub possible_responders: Vec<usize>,
+}
+
+/// A player showing a card for an accusation
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Response {
+    pub player_index: usize,
+
+    /// Only if the user has seen the card.
+    pub card_shown: Option<Card>,
+}
+
+/// An accusation as saved, which before decks named a room, suspect and
+/// weapon instead of listing the accused cards.
+#[derive(serde::Deserialize)]
+struct SavedAccusation {
+    accuser_player_index: usize,
+
+    #[serde(default)]
+    accused_cards: Vec<Card>,
+
+    room: Option<String>,
+    suspect: Option<String>,
+    weapon: Option<String>,
+
+    responding_player_index: Option<usize>,
+    card_shown: Option<Card>,
+
+    #[serde(default)]
+    responses: Vec<Response>,
+
+    #[serde(default)]
+    asked_players: Option<Vec<usize>>,
+
+    #[serde(default)]
+    possible_responders: Vec<usize>,
+}
+
+impl TryFrom<SavedAccusation> for Accusation {
+    type Error = String;
+
+    fn try_from(saved: SavedAccusation) -> std::result::Result<Accusation, String> {
+        let mut accused_cards = saved.accused_cards;
+
+        // Same order as the classic deck
+        let legacy_cards = [("RoomCard", saved.room), ("WeaponCard", saved.weapon), ("SuspectCard", saved.suspect)];
+
+        for (category_name, card_name) in legacy_cards {
+            if let Some(card_name) = card_name {
+                match get_legacy_card(category_name, &card_name) {
+                    Some(card) => accused_cards.push(card),
+                    None => return Err(format!("unknown card {} {}", category_name, card_name)),
+                }
+            }
+        }
+
+        Ok(Accusation {
+            accuser_player_index: saved.accuser_player_index,
+            accused_cards,
+            responding_player_index: saved.responding_player_index,
+            card_shown: saved.card_shown,
+            responses: saved.responses,
+            asked_players: saved.asked_players,
+            possible_responders: saved.possible_responders,
+        })
+    }
+}
+
+/// A final accusation which turned out to be wrong, so at least one of the
+/// accused cards isn't in the envelope.
+///
+/// The accuser is out of the game, they stop taking turns but still have
+/// to respond to other players' accusations.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct FinalAccusation {
+    pub accuser_player_index: usize,
+
+    /// One card of each category, in the deck's category order
+    pub accused_cards: Vec<Card>,
 }
@@ -0,0 +1,138 @@
+use crate::card_set::CardSet;
+use crate::cards::Card;
+use crate::game_state::GameState;
+
+/// Cards in your hand each player has seen, from the cards you showed them.
+///
+/// Indexed by player, empty for you and when watching the game.
+pub fn get_cards_seen_of_self(gs: &GameState) -> Vec<CardSet> {
+    let mut cards_seen = vec![CardSet::new(); gs.player_hands.len()];
+
+    let self_index = match gs.self_index {
+        Some(self_index) => self_index,
+        None => return cards_seen,
+    };
+
+    for acc in gs.accusations.iter() {
+        for response in gs.get_responses(acc) {
+            if response.player_index != self_index {
+                continue;
+            }
+
+            if let Some(card_shown) = response.card_shown {
+                cards_seen[acc.accuser_player_index].insert(card_shown);
+            }
+        }
+    }
+
+    cards_seen
+}
+
+/// Which card to show the accuser, out of the accused cards in your hand,
+/// or None if you have none of them.
+///
+/// A card the accuser has already seen tells them nothing new. Otherwise
+/// the card already seen by the most players is shown, keeping the rest of
+/// your hand secret from as many players as possible. Ties go to the first
+/// card accused.
+pub fn get_card_to_show(gs: &GameState, accuser_player_index: usize, accused_cards: &[Card]) -> Option<Card> {
+    let self_hand = &gs.player_hands[gs.self_index?];
+    let cards_seen = get_cards_seen_of_self(gs);
+
+    accused_cards.iter()
+        .filter(|card| self_hand.must_have.contains(card))
+        .min_by_key(|card| {
+            let is_new_to_accuser = !cards_seen[accuser_player_index].contains(card);
+            let number_not_seen_by = cards_seen.iter().filter(|seen| !seen.contains(card)).count();
+
+            (is_new_to_accuser, number_not_seen_by)
+        })
+        .copied()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::accusation::Accusation;
+    use crate::deck::tests::card;
+    use crate::simulation::simulate_game;
+
+    use super::*;
+
+    fn showed_self_card(accuser_player_index: usize, card_shown: Card) -> Accusation {
+        Accusation {
+            accuser_player_index,
+            accused_cards: vec![card_shown],
+            responding_player_index: Some(0),
+            card_shown: Some(card_shown),
+            responses: Vec::new(),
+            asked_players: None,
+            possible_responders: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_get_card_to_show() {
+        let mut gs = simulate_game(4, 121, 0);
+        gs.player_hands[0].must_have = [card("Kitchen"), card("Rope"), card("Green"), card("Hall")].into_iter().collect();
+
+        // Player 2 has seen the Kitchen, players 1 and 3 the Rope
+        gs.accusations = vec![
+            showed_self_card(2, card("Kitchen")),
+            showed_self_card(1, card("Rope")),
+            showed_self_card(3, card("Rope")),
+        ];
+
+        let cards_seen = get_cards_seen_of_self(&gs);
+        assert_eq!(cards_seen[2], [card("Kitchen")].into_iter().collect());
+        assert_eq!(cards_seen[3], [card("Rope")].into_iter().collect());
+        assert!(cards_seen[0].is_empty());
+
+        // Player 2 already knows about the Kitchen
+        assert_eq!(get_card_to_show(&gs, 2, &[card("Kitchen"), card("Rope"), card("Green")]), Some(card("Kitchen")));
+
+        // Player 1 has seen neither, and the Kitchen is already known to player 2
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Kitchen"), card("Knife"), card("Green")]), Some(card("Kitchen")));
+
+        // Player 2 hasn't seen the Rope, but it's already known to players 1 and 3
+        assert_eq!(get_card_to_show(&gs, 2, &[card("Hall"), card("Rope"), card("Green")]), Some(card("Rope")));
+
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Lounge"), card("Knife"), card("Plum")]), None);
+
+        gs.self_index = None;
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Kitchen"), card("Rope"), card("Green")]), None);
+    }
+
+    #[test]
+    fn test_get_card_to_show_prefers_card_already_seen() {
+        let mut gs = simulate_game(4, 122, 0);
+        gs.player_hands[0].must_have = [card("Kitchen"), card("Rope"), card("Green"), card("Hall")].into_iter().collect();
+
+        // Only player 3 has seen the Rope
+        gs.accusations = vec![showed_self_card(3, card("Rope"))];
+
+        // Showing the Rope to player 1 still keeps the Kitchen from everyone
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Kitchen"), card("Rope"), card("Plum")]), Some(card("Rope")));
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Rope"), card("Kitchen"), card("Plum")]), Some(card("Rope")));
+    }
+
+    #[test]
+    fn test_get_card_to_show_ties_go_to_first_card_accused() {
+        let mut gs = simulate_game(4, 123, 0);
+        gs.player_hands[0].must_have = [card("Kitchen"), card("Rope"), card("Green"), card("Hall")].into_iter().collect();
+
+        // Nobody has seen anything
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Kitchen"), card("Rope"), card("Green")]), Some(card("Kitchen")));
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Hall"), card("Rope"), card("Green")]), Some(card("Hall")));
+
+        // Seen by one player each
+        gs.accusations = vec![
+            showed_self_card(2, card("Kitchen")),
+            showed_self_card(3, card("Rope")),
+            showed_self_card(2, card("Green")),
+        ];
+
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Kitchen"), card("Rope"), card("Green")]), Some(card("Kitchen")));
+        assert_eq!(get_card_to_show(&gs, 1, &[card("Hall"), card("Rope"), card("Green")]), Some(card("Rope")));
+    }
+}
@@ -0,0 +1,261 @@
+use error_chain::bail;
+
+use crate::card_set::CardSet;
+use crate::deduction::Fact;
+use crate::game_state::GameState;
+use crate::holder::Holder;
+use crate::errors::*;
+
+/// Where the cards really were, as revealed at the end of the game.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Reveal {
+    /// The cards of the correct final accusation
+    pub envelope: CardSet,
+
+    /// Every player's hand, in the same order as the game's player hands,
+    /// if everyone showed their cards
+    pub player_hands: Option<Vec<CardSet>>,
+}
+
+impl Reveal {
+    /// Ensures the cards revealed could have been dealt in the game, with
+    /// no card in two places and one card of each category in the envelope.
+    pub fn verify(&self, gs: &GameState) -> Result<()> {
+        let is_one_per_category = self.envelope.len() == gs.deck.get_number_of_categories()
+            && (0..gs.deck.get_number_of_categories())
+                .all(|category_index| !gs.deck.get_all_cards_in_category(category_index).is_disjoint(&self.envelope));
+
+        if !is_one_per_category {
+            bail!(ErrorKind::InvalidReveal("the envelope doesn't hold one card of each category".to_owned()));
+        }
+
+        if !self.envelope.is_disjoint(&gs.public_cards) {
+            bail!(ErrorKind::InvalidReveal("a card in the envelope was face up".to_owned()));
+        }
+
+        let player_hands = match &self.player_hands {
+            Some(player_hands) => player_hands,
+            None => return Ok(()),
+        };
+
+        if player_hands.len() != gs.player_hands.len() {
+            bail!(ErrorKind::InvalidReveal(format!("{} hands were revealed for {} players",
+                player_hands.len(), gs.player_hands.len())));
+        }
+
+        let mut placed_cards: CardSet = &gs.public_cards | &self.envelope;
+
+        for (player_idx, hand) in player_hands.iter().enumerate() {
+            let player_name = &gs.player_hands[player_idx].player_name;
+
+            if let Some(card) = (hand & &placed_cards).iter().next() {
+                bail!(ErrorKind::InvalidReveal(format!("{} had {}, which was already face up, in the envelope or in another hand",
+                    player_name, gs.get_card_name(&card))));
+            }
+
+            if hand.len() != gs.get_hand_size(player_idx) {
+                bail!(ErrorKind::InvalidReveal(format!("{} had {} cards, rather than {}",
+                    player_name, hand.len(), gs.get_hand_size(player_idx))));
+            }
+
+            placed_cards |= hand;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the fact was true, None if the reveal doesn't say
+    pub fn is_true(&self, fact: &Fact) -> Option<bool> {
+        match fact.holder {
+            Holder::Envelope => Some(self.envelope.contains(&fact.card) == fact.has),
+            Holder::Player(player_idx) => match &self.player_hands {
+                Some(player_hands) => Some(player_hands[player_idx].contains(&fact.card) == fact.has),
+                // Nobody has a card in the envelope
+                None if self.envelope.contains(&fact.card) => Some(!fact.has),
+                None => None,
+            },
+        }
+    }
+}
+
+/// Facts in the game, whether given or deduced, which the reveal shows are wrong.
+///
+/// Facts are in holder order, players first then the envelope.
+pub fn find_wrong_facts(gs: &GameState, reveal: &Reveal) -> Vec<Fact> {
+    let mut wrong_facts: Vec<Fact> = Vec::new();
+
+    for holder in gs.get_holders() {
+        let hand = gs.get_hand(holder);
+
+        let facts = hand.must_have.iter().map(|card| Fact::new(holder, card, true))
+            .chain(hand.must_not_have.iter().map(|card| Fact::new(holder, card, false)));
+
+        wrong_facts.extend(facts.filter(|fact| reveal.is_true(fact) == Some(false)));
+    }
+
+    wrong_facts
+}
+
+/// Indices of accusations that couldn't have played out as logged with
+/// the revealed cards.
+///
+/// An accusation couldn't have happened if a card shown wasn't in the
+/// responder's hand, someone who couldn't respond had one of the cards,
+/// a responder had none of them, or the first player with one of the
+/// cards wasn't among those who might have responded.
+pub fn find_impossible_accusations(gs: &GameState, reveal: &Reveal) -> Vec<usize> {
+    let mut impossible: Vec<usize> = Vec::new();
+
+    for (acc_idx, acc) in gs.accusations.iter().enumerate() {
+        let has_card = |player_idx: usize, card| reveal.is_true(&Fact::new(Holder::Player(player_idx), card, true));
+
+        let skipped_had_card = gs.get_players_without_cards(acc)
+            .into_iter()
+            .any(|player_idx| acc.accused_cards.iter().any(|card| has_card(player_idx, *card) == Some(true)));
+
+        let responder_could_not_show = gs.get_responses(acc).into_iter()
+            .any(|response| match response.card_shown {
+                Some(card_shown) => has_card(response.player_index, card_shown) == Some(false),
+                None => acc.accused_cards.iter().all(|card| has_card(response.player_index, *card) == Some(false)),
+            });
+
+        let no_possible_responder = gs.get_possible_responder_clauses(acc).iter()
+            .any(|clause| clause.iter().all(|fact| reveal.is_true(fact) == Some(false)));
+
+        if skipped_had_card || responder_could_not_show || no_possible_responder {
+            impossible.push(acc_idx);
+        }
+    }
+
+    impossible
+}
+
+/// Indices of wrong final accusations which named every card in the
+/// revealed envelope, so weren't wrong after all.
+pub fn find_impossible_final_accusations(gs: &GameState, reveal: &Reveal) -> Vec<usize> {
+    gs.final_accusations.iter()
+        .enumerate()
+        .filter(|(_, final_accusation)| final_accusation.accused_cards.iter().all(|card| reveal.envelope.contains(card)))
+        .map(|(final_idx, _)| final_idx)
+        .collect()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::accusation::FinalAccusation;
+    use crate::cards::Card;
+    use crate::simulation::simulate_game;
+    use crate::solver::propagate_state;
+
+    use super::*;
+
+    #[test]
+    fn test_audit_finds_nothing_wrong_in_correct_games() {
+        for (number_of_players, seed) in [(3, 51), (4, 52), (5, 53), (6, 54)] {
+            let mut gs = simulate_game(number_of_players, seed, 20);
+            propagate_state(&mut gs).unwrap();
+
+            let reveal = gs.reveal.clone().unwrap();
+
+            assert_eq!(find_wrong_facts(&gs, &reveal), Vec::new());
+            assert_eq!(find_impossible_accusations(&gs, &reveal), Vec::<usize>::new());
+        }
+    }
+
+    #[test]
+    fn test_audit_flags_mistaken_accusation() {
+        let mut gs = simulate_game(4, 55, 12);
+
+        let reveal = gs.reveal.clone().unwrap();
+        let player_hands = reveal.player_hands.clone().unwrap();
+
+        // Logged someone as showing a card they don't have
+        let (acc_idx, wrong_responder) = gs.accusations.iter()
+            .enumerate()
+            .find_map(|(acc_idx, acc)| {
+                let responder = acc.responding_player_index?;
+                let card_shown = acc.card_shown?;
+
+                (0..4).find(|player_idx| *player_idx != acc.accuser_player_index && *player_idx != responder
+                        && !player_hands[*player_idx].contains(&card_shown))
+                    .map(|player_idx| (acc_idx, player_idx))
+            })
+            .unwrap();
+
+        gs.accusations[acc_idx].responding_player_index = Some(wrong_responder);
+
+        let card_shown = gs.accusations[acc_idx].card_shown.unwrap();
+        let mut propagated_gs = gs.clone();
+
+        propagate_state(&mut propagated_gs).unwrap();
+
+        assert!(find_wrong_facts(&propagated_gs, &reveal)
+            .contains(&Fact::new(Holder::Player(wrong_responder), card_shown, true)));
+
+        assert!(find_impossible_accusations(&gs, &reveal).contains(&acc_idx));
+
+        // Knowing only the envelope, a card shown from it still gives the mistake away
+        let envelope_room = gs.deck.get_all_cards_in_category(0).intersection(&reveal.envelope).iter().next().unwrap();
+        gs.accusations[acc_idx].accused_cards[0] = envelope_room;
+        gs.accusations[acc_idx].card_shown = Some(envelope_room);
+
+        let envelope_only = Reveal { envelope: reveal.envelope, player_hands: None };
+        assert!(find_impossible_accusations(&gs, &envelope_only).contains(&acc_idx));
+    }
+
+    #[test]
+    fn test_audit_flags_final_accusation_naming_envelope() {
+        let mut gs = simulate_game(4, 56, 0);
+        let reveal = gs.reveal.clone().unwrap();
+
+        let envelope_cards: Vec<Card> = reveal.envelope.iter().collect();
+        let mut wrong_cards = envelope_cards.clone();
+        wrong_cards[1] = gs.deck.get_all_cards_in_category(1).iter().find(|card| !reveal.envelope.contains(card)).unwrap();
+
+        gs.add_final_accusation(FinalAccusation { accuser_player_index: 1, accused_cards: wrong_cards });
+        assert_eq!(find_impossible_final_accusations(&gs, &reveal), Vec::<usize>::new());
+
+        // Entered as wrong, yet it was right
+        gs.add_final_accusation(FinalAccusation { accuser_player_index: 2, accused_cards: envelope_cards });
+        assert_eq!(find_impossible_final_accusations(&gs, &reveal), vec![1]);
+    }
+
+    #[test]
+    fn test_reveal_verify() {
+        let gs = simulate_game(4, 57, 0);
+        let reveal = gs.reveal.clone().unwrap();
+        let player_hands = reveal.player_hands.clone().unwrap();
+
+        assert!(reveal.verify(&gs).is_ok());
+        assert!(Reveal { envelope: reveal.envelope, player_hands: None }.verify(&gs).is_ok());
+
+        // A card in two hands
+        let card = player_hands[1].iter().next().unwrap();
+        let mut overlapping = reveal.clone();
+        overlapping.player_hands.as_mut().unwrap()[2].insert(card);
+        assert!(overlapping.verify(&gs).is_err());
+
+        // A card in the envelope and a hand
+        let mut overlapping = reveal.clone();
+        let envelope_card = reveal.envelope.iter().next().unwrap();
+        overlapping.player_hands.as_mut().unwrap()[3].remove(&player_hands[3].iter().next().unwrap());
+        overlapping.player_hands.as_mut().unwrap()[3].insert(envelope_card);
+        assert!(overlapping.verify(&gs).is_err());
+
+        // A face up card in a hand
+        if let Some(public_card) = gs.public_cards.iter().next() {
+            let mut overlapping = reveal.clone();
+            overlapping.player_hands.as_mut().unwrap()[1].insert(public_card);
+            assert!(overlapping.verify(&gs).is_err());
+        }
+
+        // Two rooms in the envelope
+        let mut two_rooms = reveal.clone();
+        let other_room = gs.deck.get_all_cards_in_category(0).iter().find(|card| !reveal.envelope.contains(card)).unwrap();
+        let envelope_weapon = (&reveal.envelope & &gs.deck.get_all_cards_in_category(1)).iter().next().unwrap();
+        two_rooms.envelope.remove(&envelope_weapon);
+        two_rooms.envelope.insert(other_room);
+        assert!(two_rooms.verify(&gs).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, Sub, SubAssign};
+
+use crate::cards::Card;
+
+/// A set of cards, stored as one bit per card in the deck.
+///
+/// Iterates in deck order, which is the same order cards sort in.
+/// Saved to JSON as a list of cards.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct CardSet {
+    bits: u64,
+}
+
+impl CardSet {
+    /// Most cards a deck can have
+    pub const MAX_CARDS: usize = 64;
+
+    pub const fn new() -> CardSet {
+        CardSet { bits: 0 }
+    }
+
+    fn get_bit(card: &Card) -> u64 {
+        1 << card.get_index()
+    }
+
+    /// Returns whether the card wasn't already in the set.
+    pub fn insert(&mut self, card: Card) -> bool {
+        let bit = CardSet::get_bit(&card);
+        let is_new = self.bits & bit == 0;
+
+        self.bits |= bit;
+        is_new
+    }
+
+    /// Returns whether the card was in the set.
+    pub fn remove(&mut self, card: &Card) -> bool {
+        let bit = CardSet::get_bit(card);
+        let was_present = self.bits & bit != 0;
+
+        self.bits &= !bit;
+        was_present
+    }
+
+    pub fn contains(&self, card: &Card) -> bool {
+        self.bits & CardSet::get_bit(card) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.bits = 0;
+    }
+
+    pub fn iter(&self) -> CardSetIter {
+        CardSetIter { bits: self.bits }
+    }
+
+    pub fn union(&self, other: &CardSet) -> CardSet {
+        CardSet { bits: self.bits | other.bits }
+    }
+
+    pub fn intersection(&self, other: &CardSet) -> CardSet {
+        CardSet { bits: self.bits & other.bits }
+    }
+
+    pub fn difference(&self, other: &CardSet) -> CardSet {
+        CardSet { bits: self.bits & !other.bits }
+    }
+
+    pub fn is_subset(&self, other: &CardSet) -> bool {
+        self.bits & !other.bits == 0
+    }
+
+    pub fn is_superset(&self, other: &CardSet) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &CardSet) -> bool {
+        self.bits & other.bits == 0
+    }
+
+    /// Keeps only the cards the predicate is true for.
+    pub fn retain<F: FnMut(&Card) -> bool>(&mut self, mut keep: F) {
+        for card in self.iter() {
+            if !keep(&card) {
+                self.remove(&card);
+            }
+        }
+    }
+}
+
+/// Iterates over the cards of a CardSet in deck order
+pub struct CardSetIter {
+    bits: u64,
+}
+
+impl Iterator for CardSetIter {
+    type Item = Card;
+
+    fn next(&mut self) -> Option<Card> {
+        if self.bits == 0 {
+            return None;
+        }
+
+        let index = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+
+        Some(Card::from_index(index))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.bits.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for CardSetIter {}
+
+impl IntoIterator for CardSet {
+    type Item = Card;
+    type IntoIter = CardSetIter;
+
+    fn into_iter(self) -> CardSetIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for &CardSet {
+    type Item = Card;
+    type IntoIter = CardSetIter;
+
+    fn into_iter(self) -> CardSetIter {
+        self.iter()
+    }
+}
+
+impl FromIterator<Card> for CardSet {
+    fn from_iter<I: IntoIterator<Item = Card>>(iter: I) -> CardSet {
+        let mut set = CardSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<Card> for CardSet {
+    fn extend<I: IntoIterator<Item = Card>>(&mut self, iter: I) {
+        for card in iter {
+            self.insert(card);
+        }
+    }
+}
+
+impl<const N: usize> From<[Card; N]> for CardSet {
+    fn from(cards: [Card; N]) -> CardSet {
+        cards.into_iter().collect()
+    }
+}
+
+impl BitOr<&CardSet> for &CardSet {
+    type Output = CardSet;
+
+    fn bitor(self, other: &CardSet) -> CardSet {
+        self.union(other)
+    }
+}
+
+impl BitAnd<&CardSet> for &CardSet {
+    type Output = CardSet;
+
+    fn bitand(self, other: &CardSet) -> CardSet {
+        self.intersection(other)
+    }
+}
+
+impl Sub<&CardSet> for &CardSet {
+    type Output = CardSet;
+
+    fn sub(self, other: &CardSet) -> CardSet {
+        self.difference(other)
+    }
+}
+
+impl BitOrAssign<&CardSet> for CardSet {
+    fn bitor_assign(&mut self, other: &CardSet) {
+        self.bits |= other.bits;
+    }
+}
+
+impl BitAndAssign<&CardSet> for CardSet {
+    fn bitand_assign(&mut self, other: &CardSet) {
+        self.bits &= other.bits;
+    }
+}
+
+impl SubAssign<&CardSet> for CardSet {
+    fn sub_assign(&mut self, other: &CardSet) {
+        self.bits &= !other.bits;
+    }
+}
+
+impl fmt::Debug for CardSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.iter()).finish()
+    }
+}
+
+impl serde::Serialize for CardSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for CardSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<CardSet, D::Error> {
+        let cards: Vec<Card> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(cards.into_iter().collect())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::deck::{Deck, tests::card};
+
+    use super::*;
+
+    #[test]
+    fn test_card_set_iterates_in_deck_order() {
+        let cards: CardSet = Deck::classic().get_all_cards();
+
+        let mut sorted_cards: Vec<Card> = cards.iter().collect();
+        sorted_cards.sort();
+
+        assert_eq!(cards.len(), Deck::classic().get_total_cards());
+        assert_eq!(cards.iter().collect::<Vec<Card>>(), sorted_cards);
+    }
+
+    #[test]
+    fn test_card_set_algebra() {
+        let mut a = CardSet::from([card("Kitchen"), card("Rope")]);
+        let b = CardSet::from([card("Rope"), card("White")]);
+
+        assert_eq!(&a | &b, CardSet::from([
+            card("Kitchen"), card("Rope"), card("White")]));
+        assert_eq!(&a & &b, CardSet::from([card("Rope")]));
+        assert_eq!(&a - &b, CardSet::from([card("Kitchen")]));
+        assert!(!a.is_disjoint(&b));
+        assert!((&a & &b).is_subset(&a));
+
+        assert!(!a.insert(card("Kitchen")));
+        assert!(a.remove(&card("Kitchen")));
+        assert!(!a.contains(&card("Kitchen")));
+        assert_eq!(a.len(), 1);
+    }
+
+    #[test]
+    fn test_card_set_json() {
+        let cards = CardSet::from([card("Kitchen"), card("White")]);
+
+        assert_eq!(serde_json::to_string(&cards).unwrap(), "[0,20]");
+        assert_eq!(serde_json::from_str::<CardSet>("[20,0]").unwrap(), cards);
+        assert!(serde_json::from_str::<CardSet>("[20,64]").is_err());
+
+        // The format save files were written in before decks
+        let json = r#"[{"SuspectCard":"White"},{"RoomCard":"Kitchen"}]"#;
+        assert_eq!(serde_json::from_str::<CardSet>(json).unwrap(), cards);
+    }
+}
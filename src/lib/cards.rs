@@ -1,119 +1,66 @@
-use std::{collections::HashSet, fmt};
+use std::fmt;
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone,serde::Serialize, serde::Deserialize)]
-pub enum Card {
-    RoomCard(Room),
-    WeaponCard(Weapon),
-    SuspectCard(Suspect),
-}
+use crate::card_set::CardSet;
+use crate::deck::get_legacy_card;
+
+/// A card, stored as its position in the deck.
+///
+/// The game's Deck gives the card's name and category.
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct Card(u8);
 
 impl Card {
-    pub fn get_total_cards() -> usize { 
-        enum_iterator::cardinality::<Room>() + 
-        enum_iterator::cardinality::<Suspect>() +
-        enum_iterator::cardinality::<Weapon>()
+    /// The card at a position in the deck
+    pub fn from_index(index: usize) -> Card {
+        Card(index as u8)
     }
 
-    pub fn variant_eq(&self, b: &Self) -> bool {
-        std::mem::discriminant(self) == std::mem::discriminant(b)
+    /// Position of the card in the deck, in the order cards sort in.
+    pub fn get_index(&self) -> usize {
+        self.0 as usize
     }
+}
 
-    pub fn get_all_cards() -> HashSet<Card> {
-        let all_cards = 
-            enum_iterator::all::<Room>()
-                .map(Card::RoomCard)
-            .chain(enum_iterator::all::<Suspect>()
-                .map(Card::SuspectCard))
-            .chain(enum_iterator::all::<Weapon>()
-                .map(Card::WeaponCard));
-
-        all_cards.collect()
+impl serde::Serialize for Card {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_u8(self.0)
     }
 }
 
-impl fmt::Display for Card {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Card::RoomCard(room) => write!(f,"{}", room),
-            Card::WeaponCard(weapon) => write!(f,"{}", weapon),
-            Card::SuspectCard(suspect) => write!(f,"{}", suspect),
-        }
+impl<'de> serde::Deserialize<'de> for Card {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Card, D::Error> {
+        deserializer.deserialize_any(CardVisitor)
     }
 }
 
+/// Reads a card as its index, or as it was saved before decks,
+/// like {"RoomCard": "Kitchen"}.
+struct CardVisitor;
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, enum_iterator::Sequence,serde::Serialize, serde::Deserialize)]
-pub enum Suspect {
-    Mustard,
-    Plum,
-    Green,
-    Peacock,
-    Scarlet,
-    White
-}
+impl<'de> serde::de::Visitor<'de> for CardVisitor {
+    type Value = Card;
 
-impl fmt::Display for Suspect {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Suspect::Scarlet => write!(f, "Miss Scarlet"),
-            Suspect::Green => write!(f, "Mr. Green"),
-            Suspect::Mustard => write!(f, "Colonel Mustard"),
-            Suspect::Plum => write!(f, "Professor Plum"),
-            Suspect::Peacock => write!(f, "Mrs. Peacock"),
-            Suspect::White => write!(f, "Mrs. White")
-        }
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a card index")
     }
-}
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, enum_iterator::Sequence,serde::Serialize, serde::Deserialize)]
-pub enum Weapon {
-    Rope,
-    Candlestick,
-    Knife,
-    Pipe,
-    Pistol,
-    Wrench
-}
-
-impl fmt::Display for Weapon {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Weapon::Rope => write!(f, "Rope"),
-            Weapon::Candlestick => write!(f, "Candlestick"),
-            Weapon::Knife => write!(f, "Knife"),
-            Weapon::Pipe => write!(f, "Lead Pipe"),
-            Weapon::Pistol => write!(f, "Pistol"),
-            Weapon::Wrench => write!(f, "Wrench")
+    fn visit_u64<E: serde::de::Error>(self, index: u64) -> std::result::Result<Card, E> {
+        if index >= CardSet::MAX_CARDS as u64 {
+            return Err(E::custom(format!("card index {} is past the last card any deck can have", index)));
         }
-    }
-}
 
-#[derive(Debug, Eq, PartialEq, Hash, Clone, enum_iterator::Sequence,serde::Serialize, serde::Deserialize)]
-pub enum Room {
-    Kitchen,
-    Ballroom,
-    Conservatory,
-    Dining,
-    Lounge,
-    Hall,
-    Study,
-    Library,
-    Billiard
-}
+        Ok(Card(index as u8))
+    }
 
+    fn visit_map<A: serde::de::MapAccess<'de>>(self, mut map: A) -> std::result::Result<Card, A::Error> {
+        let (category_name, card_name): (String, String) = match map.next_entry()? {
+            Some(entry) => entry,
+            None => return Err(serde::de::Error::custom("expected a card")),
+        };
 
-impl fmt::Display for Room {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Room::Kitchen => write!(f, "Kitchen"),
-            Room::Ballroom => write!(f, "Ballroom"),
-            Room::Conservatory => write!(f, "Conservatory"),
-            Room::Dining => write!(f, "Dining Room"),
-            Room::Lounge => write!(f, "Lounge"),
-            Room::Hall => write!(f, "Hall"),
-            Room::Study => write!(f, "Study"),
-            Room::Library => write!(f, "Library"),
-            Room::Billiard => write!(f, "Billiard Room"),
+        match get_legacy_card(&category_name, &card_name) {
+            Some(card) => Ok(card),
+            None => Err(serde::de::Error::custom(format!("unknown card {} {}", category_name, card_name))),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,674 @@
+use std::collections::HashMap;
+
+use error_chain::bail;
+
+use crate::cards::*;
+use crate::game_state::GameState;
+use crate::holder::Holder;
+use crate::deduction::{Deduction, Fact, Rule};
+use crate::errors::*;
+
+/// Finds every fact that is true in all deals consistent with the game state,
+/// and adds them to the players' and the envelope's must have and must not have,
+/// recording each new one in the deductions.
+///
+/// Unlike solver::propagate_state, which applies a fixed set of rules, this
+/// searches over every deal, so nothing that can be deduced is missed.
+///
+/// Can be used in place of solver::propagate_state.
+pub fn propagate_state_complete(gs: &mut GameState) -> Result<()> {
+    let model = ConstraintModel::from_game_state(gs, None);
+
+    apply_forced_values(gs, &model, Rule::EveryConsistentDeal)
+}
+
+/// Like propagate_state_complete, but allows up to tolerance accusations to be
+/// wrong. Only facts that are true no matter which accusations are ignored
+/// are added.
+///
+/// Facts already in the hands are trusted, so they should only be the given
+/// ones, not ones deduced from the accusations.
+pub fn propagate_state_tolerant(gs: &mut GameState, tolerance: usize) -> Result<()> {
+    let model = ConstraintModel::from_game_state(gs, Some(tolerance));
+
+    apply_forced_values(gs, &model, Rule::EveryDealAllowingMistakes)
+}
+
+fn apply_forced_values(gs: &mut GameState, model: &ConstraintModel, rule: Rule) -> Result<()> {
+    let facts = match model.find_forced_values() {
+        Some(facts) => facts,
+        None => bail!(ErrorKind::NoConsistentDeals),
+    };
+
+    for (var, value) in facts.iter().enumerate().take(model.number_of_vars()) {
+        let (card, holder) = model.get_card_and_holder(var);
+
+        if let Some(has) = value {
+            gs.add_deduction(Deduction::new(Fact::new(holder, *card, *has), rule.clone()));
+        }
+    }
+
+    // Public cards aren't anywhere
+    for hand in gs.player_hands.iter_mut().chain(std::iter::once(&mut gs.envelope)) {
+        hand.must_not_have |= &gs.public_cards;
+    }
+
+    // Tidy up groups of cards which are now decided
+    for hand in gs.player_hands.iter_mut() {
+        let must_have = &hand.must_have;
+        let must_not_have = &hand.must_not_have;
+
+        hand.must_have_one_of = hand.must_have_one_of.iter()
+            .filter(|cards| cards.is_disjoint(must_have))
+            .map(|cards| {
+                let possible_cards = cards - must_not_have;
+                if possible_cards.is_empty() { *cards } else { possible_cards }
+            })
+            .collect();
+    }
+
+    Ok(())
+}
+
+/// Whether there is at least one deal consistent with the game state
+pub fn is_consistent(gs: &GameState) -> bool {
+    let model = ConstraintModel::from_game_state(gs, None);
+
+    match model.propagate_root() {
+        Some(values) => model.solve(values).is_some(),
+        None => false,
+    }
+}
+
+
+/// A rule over the boolean variables.
+#[derive(Debug, Clone)]
+enum Constraint {
+    /// Exactly count of the variables are true
+    Exactly { vars: Vec<usize>, count: usize },
+
+    /// At least one of the variables has the given value
+    AtLeastOne(Vec<(usize, bool)>),
+
+    /// The variable has the given value
+    Fixed(usize, bool),
+}
+
+/// The game as a set of boolean variables, one per card and holder, which is
+/// true if the holder has the card. Holders are the players, then the envelope.
+///
+/// Public cards are left out, since they aren't anywhere.
+///
+/// When allowing for mistakes, each accusation also gets a variable which is
+/// true if the accusation holds, followed by spare variables which let up to
+/// the tolerance of them be false.
+struct ConstraintModel {
+    cards: Vec<Card>,
+    number_of_holders: usize,
+    number_of_mistake_vars: usize,
+    constraints: Vec<Constraint>,
+
+    /// Constraints which mention each variable
+    watches: Vec<Vec<usize>>,
+}
+
+impl ConstraintModel {
+    fn from_game_state(gs: &GameState, tolerance: Option<usize>) -> ConstraintModel {
+        let number_of_players = gs.player_hands.len();
+        let number_of_holders = number_of_players + 1;
+        let envelope_idx = number_of_players;
+
+        let mut cards: Vec<Card> = (&gs.deck.get_all_cards() - &gs.public_cards).into_iter().collect();
+        cards.sort();
+
+        let card_index: HashMap<&Card, usize> = cards.iter()
+            .enumerate()
+            .map(|(idx, card)| (card, idx))
+            .collect();
+
+        let var = |card_idx: usize, holder_idx: usize| card_idx * number_of_holders + holder_idx;
+
+        let mut constraints: Vec<Constraint> = Vec::new();
+
+        // Every card is with exactly one holder
+        for card_idx in 0..cards.len() {
+            constraints.push(Constraint::Exactly {
+                vars: (0..number_of_holders).map(|holder_idx| var(card_idx, holder_idx)).collect(),
+                count: 1,
+            });
+        }
+
+        // Every hand is full
+        for player_idx in 0..number_of_players {
+            constraints.push(Constraint::Exactly {
+                vars: (0..cards.len()).map(|card_idx| var(card_idx, player_idx)).collect(),
+                count: gs.get_hand_size(player_idx),
+            });
+        }
+
+        // The envelope has one card of each category
+        for category_index in 0..gs.deck.get_number_of_categories() {
+            constraints.push(Constraint::Exactly {
+                vars: (0..cards.len())
+                    .filter(|card_idx| gs.deck.get_category_index(&cards[*card_idx]) == category_index)
+                    .map(|card_idx| var(card_idx, envelope_idx))
+                    .collect(),
+                count: 1,
+            });
+        }
+
+        // What we already know about each hand
+        let hands = gs.player_hands.iter().chain(std::iter::once(&gs.envelope));
+        for (holder_idx, hand) in hands.enumerate() {
+            for card in hand.must_have.iter() {
+                match card_index.get(&card) {
+                    Some(&card_idx) => constraints.push(Constraint::Fixed(var(card_idx, holder_idx), true)),
+                    // Has a public card, which can't happen
+                    None => constraints.push(Constraint::AtLeastOne(Vec::new())),
+                }
+            }
+
+            for card in hand.must_not_have.iter() {
+                if let Some(&card_idx) = card_index.get(&card) {
+                    constraints.push(Constraint::Fixed(var(card_idx, holder_idx), false));
+                }
+            }
+
+            for cards in hand.must_have_one_of.iter() {
+                constraints.push(Constraint::AtLeastOne(cards.iter()
+                    .filter_map(|card| card_index.get(&card))
+                    .map(|&card_idx| (var(card_idx, holder_idx), true))
+                    .collect()));
+            }
+        }
+
+        for observation in gs.observations.iter() {
+            match card_index.get(&observation.card) {
+                Some(&card_idx) => constraints.push(Constraint::Fixed(var(card_idx, observation.player_index), observation.has)),
+                // Seen a public card in a hand, which can't happen
+                None if observation.has => constraints.push(Constraint::AtLeastOne(Vec::new())),
+                None => {},
+            }
+        }
+
+        // Wrong final accusations, a named card isn't in the envelope. If one is
+        // face up that's already true.
+        for final_accusation in gs.final_accusations.iter() {
+            let accused_card_indices: Option<Vec<usize>> = final_accusation.accused_cards.iter()
+                .map(|card| card_index.get(&card).cloned())
+                .collect();
+
+            if let Some(accused_card_indices) = accused_card_indices {
+                constraints.push(Constraint::AtLeastOne(accused_card_indices.into_iter()
+                    .map(|card_idx| (var(card_idx, envelope_idx), false))
+                    .collect()));
+            }
+        }
+
+        // Variables for each accusation holding, then the spare variables
+        let number_of_card_vars = cards.len() * number_of_holders;
+        let number_of_mistake_vars = match tolerance {
+            Some(tolerance) => gs.accusations.len() + tolerance,
+            None => 0,
+        };
+
+        if let Some(tolerance) = tolerance {
+            constraints.push(Constraint::Exactly {
+                vars: (number_of_card_vars..(number_of_card_vars + gs.accusations.len() + tolerance)).collect(),
+                count: gs.accusations.len(),
+            });
+        }
+
+        for (acc_idx, acc) in gs.accusations.iter().enumerate() {
+            let mut accusation_constraints: Vec<Constraint> = Vec::new();
+
+            let accused_card_indices: Vec<usize> = acc.accused_cards.iter()
+                .filter_map(|card| card_index.get(&card))
+                .cloned()
+                .collect();
+
+            // Players who were asked and could not respond
+            for player_idx in gs.get_players_without_cards(acc) {
+                for card_idx in accused_card_indices.iter() {
+                    accusation_constraints.push(Constraint::Fixed(var(*card_idx, player_idx), false));
+                }
+            }
+
+            for response in gs.get_responses(acc) {
+                let responding_player_idx = response.player_index;
+
+                match response.card_shown.as_ref().map(|card| card_index.get(&card)) {
+                    Some(Some(&card_idx)) =>
+                        accusation_constraints.push(Constraint::Fixed(var(card_idx, responding_player_idx), true)),
+                    // Showed a public card, which can't happen
+                    Some(None) => accusation_constraints.push(Constraint::AtLeastOne(Vec::new())),
+                    None => accusation_constraints.push(Constraint::AtLeastOne(accused_card_indices.iter()
+                        .map(|card_idx| (var(*card_idx, responding_player_idx), true))
+                        .collect())),
+                }
+            }
+
+            // Public cards aren't in any hand
+            for clause in gs.get_possible_responder_clauses(acc) {
+                if clause.iter().any(|fact| !fact.has && !card_index.contains_key(&fact.card)) {
+                    continue;
+                }
+
+                accusation_constraints.push(Constraint::AtLeastOne(clause.iter()
+                    .filter_map(|fact| match (fact.holder, card_index.get(&fact.card)) {
+                        (Holder::Player(player_idx), Some(&card_idx)) => Some((var(card_idx, player_idx), fact.has)),
+                        _ => None,
+                    })
+                    .collect()));
+            }
+
+            if tolerance.is_none() {
+                constraints.extend(accusation_constraints);
+                continue;
+            }
+
+            // The accusation's constraints only apply if it holds
+            let holds_var = number_of_card_vars + acc_idx;
+
+            constraints.extend(accusation_constraints.into_iter().map(|constraint| match constraint {
+                Constraint::Fixed(var, value) => Constraint::AtLeastOne(vec![(holds_var, false), (var, value)]),
+                Constraint::AtLeastOne(mut literals) => {
+                    literals.push((holds_var, false));
+                    Constraint::AtLeastOne(literals)
+                }
+                exactly => exactly,
+            }));
+        }
+
+        let mut watches: Vec<Vec<usize>> = vec![Vec::new(); number_of_card_vars + number_of_mistake_vars];
+
+        for (constraint_idx, constraint) in constraints.iter().enumerate() {
+            let vars: Vec<usize> = match constraint {
+                Constraint::Exactly { vars, .. } => vars.clone(),
+                Constraint::AtLeastOne(literals) => literals.iter().map(|(var, _)| *var).collect(),
+                Constraint::Fixed(var, _) => vec![*var],
+            };
+
+            for var in vars {
+                watches[var].push(constraint_idx);
+            }
+        }
+
+        ConstraintModel { cards, number_of_holders, number_of_mistake_vars, constraints, watches }
+    }
+
+    /// Number of card variables, which come before any mistake variables
+    fn number_of_vars(&self) -> usize {
+        self.cards.len() * self.number_of_holders
+    }
+
+    fn get_card_and_holder(&self, var: usize) -> (&Card, Holder) {
+        let card = &self.cards[var / self.number_of_holders];
+        let holder_idx = var % self.number_of_holders;
+
+        if holder_idx == self.number_of_holders - 1 {
+            (card, Holder::Envelope)
+        } else {
+            (card, Holder::Player(holder_idx))
+        }
+    }
+
+    /// Values forced by the constraints alone, or None if they contradict.
+    fn propagate_root(&self) -> Option<Vec<Option<bool>>> {
+        let mut values = vec![None; self.number_of_vars() + self.number_of_mistake_vars];
+
+        for constraint_idx in 0..self.constraints.len() {
+            let mut queue: Vec<usize> = Vec::new();
+
+            if !self.check_constraint(constraint_idx, &mut values, &mut queue)
+                || !self.propagate(&mut values, queue) {
+                return None;
+            }
+        }
+
+        Some(values)
+    }
+
+    /// Sets every value forced by the newly set variables in the queue.
+    ///
+    /// Returns false if a constraint can't be met.
+    fn propagate(&self, values: &mut [Option<bool>], mut queue: Vec<usize>) -> bool {
+        while let Some(var) = queue.pop() {
+            for &constraint_idx in self.watches[var].iter() {
+                if !self.check_constraint(constraint_idx, values, &mut queue) {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    fn check_constraint(&self, constraint_idx: usize, values: &mut [Option<bool>], queue: &mut Vec<usize>) -> bool {
+        let mut set = |values: &mut [Option<bool>], var: usize, value: bool| -> bool {
+            match values[var] {
+                Some(existing) => existing == value,
+                None => {
+                    values[var] = Some(value);
+                    queue.push(var);
+                    true
+                }
+            }
+        };
+
+        match &self.constraints[constraint_idx] {
+            Constraint::Fixed(var, value) => set(values, *var, *value),
+
+            Constraint::Exactly { vars, count } => {
+                let number_true = vars.iter().filter(|var| values[**var] == Some(true)).count();
+                let number_unknown = vars.iter().filter(|var| values[**var].is_none()).count();
+
+                if number_true > *count || number_true + number_unknown < *count {
+                    return false;
+                }
+
+                if number_unknown > 0 && (number_true == *count || number_true + number_unknown == *count) {
+                    // The rest are all decided
+                    let value = number_true < *count;
+                    for var in vars.iter() {
+                        if values[*var].is_none() {
+                            set(values, *var, value);
+                        }
+                    }
+                }
+
+                true
+            }
+
+            Constraint::AtLeastOne(literals) => {
+                if literals.iter().any(|(var, value)| values[*var] == Some(*value)) {
+                    return true;
+                }
+
+                let mut unknown = literals.iter().filter(|(var, _)| values[*var].is_none());
+
+                match (unknown.next(), unknown.next()) {
+                    (None, _) => false,
+                    (Some((var, value)), None) => set(values, *var, *value),
+                    _ => true,
+                }
+            }
+        }
+    }
+
+    /// Finds a full assignment that meets every constraint, starting from
+    /// already propagated values.
+    fn solve(&self, values: Vec<Option<bool>>) -> Option<Vec<Option<bool>>> {
+        // Branch on the card with the fewest holders left
+        let mut branch_var: Option<usize> = None;
+        let mut fewest_options = usize::MAX;
+
+        for card_idx in 0..self.cards.len() {
+            let card_vars = (card_idx * self.number_of_holders)..((card_idx + 1) * self.number_of_holders);
+
+            if card_vars.clone().any(|var| values[var] == Some(true)) {
+                continue;
+            }
+
+            let unknown: Vec<usize> = card_vars.filter(|var| values[*var].is_none()).collect();
+
+            if !unknown.is_empty() && unknown.len() < fewest_options {
+                fewest_options = unknown.len();
+                branch_var = Some(unknown[0]);
+            }
+        }
+
+        let branch_var = match branch_var {
+            Some(var) => var,
+            // Every card is placed, and propagation checked every constraint
+            None => return Some(values),
+        };
+
+        for value in [true, false] {
+            let mut branch_values = values.clone();
+            branch_values[branch_var] = Some(value);
+
+            if self.propagate(&mut branch_values, vec![branch_var]) {
+                if let Some(solution) = self.solve(branch_values) {
+                    return Some(solution);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Values every consistent deal agrees on, or None if there is no consistent deal.
+    fn find_forced_values(&self) -> Option<Vec<Option<bool>>> {
+        let mut root = self.propagate_root()?;
+        let first_solution = self.solve(root.clone())?;
+
+        // Variables that might have the same value in every solution
+        let mut candidates: Vec<bool> = root.iter().map(|value| value.is_none()).collect();
+
+        for var in 0..self.number_of_vars() {
+            if !candidates[var] || root[var].is_some() {
+                continue;
+            }
+
+            let solution_value = first_solution[var].unwrap();
+
+            let mut trial = root.clone();
+            trial[var] = Some(!solution_value);
+
+            let other_solution = if self.propagate(&mut trial, vec![var]) {
+                self.solve(trial)
+            } else {
+                None
+            };
+
+            match other_solution {
+                Some(other_solution) => {
+                    // Anything that differs between the two solutions isn't forced
+                    for (other_var, candidate) in candidates.iter_mut().enumerate() {
+                        if other_solution[other_var] != first_solution[other_var] {
+                            *candidate = false;
+                        }
+                    }
+                }
+                None => {
+                    // Must have the value from the first solution
+                    root[var] = Some(solution_value);
+                    if !self.propagate(&mut root, vec![var]) {
+                        return None;
+                    }
+                }
+            }
+        }
+
+        Some(root)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::accusation::{FinalAccusation, Response};
+    use crate::observation::Observation;
+    use crate::probability::get_card_probabilities;
+    use crate::rules::ResponseRule;
+    use crate::deck::Deck;
+    use crate::simulation::{miss_responders, simulate_game, simulate_game_with_deck};
+    use crate::solver::{get_responding_players, propagate_state};
+
+    use super::*;
+
+    /// Propagates the game with every possible deal, checking it finds the
+    /// same certain facts as the probabilities, and at least those the rules
+    /// find. Returns the propagated game.
+    fn assert_probabilities_match_propagation(gs: &GameState) -> GameState {
+        let probabilities = get_card_probabilities(gs).unwrap();
+
+        let mut rules_gs = gs.clone();
+        propagate_state(&mut rules_gs).unwrap();
+
+        let mut complete_gs = gs.clone();
+        propagate_state_complete(&mut complete_gs).unwrap();
+
+        for holder in complete_gs.get_holders() {
+            let hand = complete_gs.get_hand(holder);
+
+            for card in &complete_gs.deck.get_all_cards() - &complete_gs.public_cards {
+                let probability = probabilities.get_probability(&card, holder);
+
+                assert_eq!(hand.must_have.contains(&card), probability == 1.0, "{:?} {:?}", card, holder);
+                assert_eq!(hand.must_not_have.contains(&card), probability == 0.0, "{:?} {:?}", card, holder);
+            }
+
+            let rules_hand = rules_gs.get_hand(holder);
+            assert!(rules_hand.must_have.is_subset(&hand.must_have), "{:?}", holder);
+            assert!(rules_hand.must_not_have.is_subset(&hand.must_not_have), "{:?}", holder);
+        }
+
+        complete_gs
+    }
+
+    #[test]
+    fn test_propagate_state_complete_finds_every_certain_fact() {
+        for (number_of_players, seed) in [(3, 1), (4, 2), (5, 3), (6, 4), (6, 5)] {
+            let gs = simulate_game(number_of_players, seed, 12);
+            let reveal = gs.reveal.clone().unwrap();
+
+            assert_probabilities_match_propagation(&gs).verify_state().unwrap();
+
+            // Someone made a wrong final accusation
+            let mut final_accusation_gs = gs.clone();
+            let wrong_cards = gs.accusations.iter()
+                .map(|acc| acc.accused_cards.clone())
+                .find(|accused_cards| !accused_cards.iter().all(|card| reveal.envelope.contains(card)))
+                .unwrap();
+            final_accusation_gs.final_accusations.push(FinalAccusation { accuser_player_index: 1, accused_cards: wrong_cards });
+
+            assert_probabilities_match_propagation(&final_accusation_gs);
+
+            // Everyone who can shows a card, we only see the ones shown to us
+            let mut everyone_shows_gs = gs.clone();
+            everyone_shows_gs.rules.response_rule = ResponseRule::EveryoneShows;
+
+            let hands = reveal.player_hands.clone().unwrap();
+
+            for acc in everyone_shows_gs.accusations.iter_mut() {
+                let seen = gs.is_self(acc.accuser_player_index);
+
+                acc.responses = get_responding_players(acc.accuser_player_index, None, number_of_players).into_iter()
+                    .filter_map(|player_idx| {
+                        let card = acc.accused_cards.iter().find(|card| hands[player_idx].contains(card))?;
+
+                        Some(Response { player_index: player_idx, card_shown: (seen || gs.is_self(player_idx)).then_some(*card) })
+                    })
+                    .collect();
+
+                acc.responding_player_index = None;
+                acc.card_shown = None;
+            }
+
+            everyone_shows_gs.verify_state().unwrap();
+            assert_probabilities_match_propagation(&everyone_shows_gs);
+
+            // A player left the game, showing their hand
+            let mut observations_gs = gs.clone();
+            observations_gs.observations.extend(hands[1].iter()
+                .map(|card| Observation { player_index: 1, card, has: true }));
+
+            assert_probabilities_match_propagation(&observations_gs);
+
+            // We missed who responded to some accusations
+            let mut missed_responders_gs = gs.clone();
+            miss_responders(&mut missed_responders_gs);
+
+            missed_responders_gs.verify_state().unwrap();
+            assert_probabilities_match_propagation(&missed_responders_gs);
+
+            // Watching, so no hand is known and every card is shown in secret
+            let mut spectated_gs = gs.clone();
+            spectated_gs.self_index = None;
+            spectated_gs.player_hands[0].must_have.clear();
+
+            for acc in spectated_gs.accusations.iter_mut() {
+                acc.card_shown = None;
+            }
+
+            spectated_gs.verify_state().unwrap();
+            assert_probabilities_match_propagation(&spectated_gs);
+
+            // Every card was dealt, even when some hands get a card more
+            let uneven_gs = simulate_game_with_deck(Deck::classic(), number_of_players, seed, 12, true);
+            assert_probabilities_match_propagation(&uneven_gs).verify_state().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_propagate_state_complete_includes_propagate_state() {
+        for (number_of_players, seed) in [(3, 6), (4, 7), (5, 8), (6, 9)] {
+            let mut rules_gs = simulate_game(number_of_players, seed, 15);
+            let mut complete_gs = simulate_game(number_of_players, seed, 15);
+
+            propagate_state(&mut rules_gs).unwrap();
+            propagate_state_complete(&mut complete_gs).unwrap();
+
+            for holder in rules_gs.get_holders() {
+                let rules_hand = rules_gs.get_hand(holder);
+                let complete_hand = complete_gs.get_hand(holder);
+
+                assert!(rules_hand.must_have.is_subset(&complete_hand.must_have));
+                assert!(rules_hand.must_not_have.is_subset(&complete_hand.must_not_have));
+            }
+        }
+    }
+
+    #[test]
+    fn test_is_consistent() {
+        let mut gs = simulate_game(4, 10, 10);
+        assert!(is_consistent(&gs));
+
+        // Someone who couldn't respond can't have shown a card
+        let (room, player_idx) = gs.accusations.iter()
+            .find_map(|acc| get_responding_players(acc.accuser_player_index, acc.responding_player_index, 4)
+                .first()
+                .map(|player_idx| (acc.accused_cards[0], *player_idx)))
+            .unwrap();
+
+        gs.player_hands[player_idx].must_have.insert(room);
+        assert!(!is_consistent(&gs));
+    }
+
+    #[test]
+    fn test_propagate_state_tolerant_matches_ignoring_each_accusation() {
+        for (number_of_players, seed) in [(3, 11), (4, 12), (5, 13)] {
+            let mut gs = simulate_game(number_of_players, seed, 8);
+
+            // Someone forgot to respond
+            gs.accusations[2].responding_player_index = None;
+            gs.accusations[2].card_shown = None;
+
+            let mut tolerant_gs = gs.clone();
+            propagate_state_tolerant(&mut tolerant_gs, 1).unwrap();
+
+            // Facts found when ignoring each accusation in turn
+            let mut ignoring_each: Vec<GameState> = Vec::new();
+            for acc_idx in 0..gs.accusations.len() {
+                let mut ignoring_gs = gs.clone();
+                ignoring_gs.accusations.remove(acc_idx);
+
+                if propagate_state_complete(&mut ignoring_gs).is_ok() {
+                    ignoring_each.push(ignoring_gs);
+                }
+            }
+
+            for holder in gs.get_holders() {
+                for card in &gs.deck.get_all_cards() - &gs.public_cards {
+                    let always_has = ignoring_each.iter().all(|other| other.get_hand(holder).must_have.contains(&card));
+                    let never_has = ignoring_each.iter().all(|other| other.get_hand(holder).must_not_have.contains(&card));
+
+                    assert_eq!(tolerant_gs.get_hand(holder).must_have.contains(&card), always_has, "{:?} {:?}", card, holder);
+                    assert_eq!(tolerant_gs.get_hand(holder).must_not_have.contains(&card), never_has, "{:?} {:?}", card, holder);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,454 @@
+use std::{io::Read, fs::File};
+
+use error_chain::bail;
+
+use crate::cards::Card;
+use crate::card_set::CardSet;
+use crate::rules::PlayDirection;
+use crate::errors::*;
+
+/// The cards a game is played with, split into categories.
+///
+/// The envelope holds one card of each category, and an accusation names
+/// one card of each category. Cards are numbered in the order they're
+/// listed, category by category.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Deck {
+    pub name: String,
+    pub categories: Vec<Category>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Category {
+    /// Plural name of the category, like "Rooms"
+    pub name: String,
+
+    pub cards: Vec<String>,
+}
+
+/// Names of the built in editions, for Deck::from_edition
+pub const EDITIONS: [&str; 4] = ["classic", "uk", "2016", "master"];
+
+/// Names of the cards in saves from before decks, which are always the
+/// classic deck. Listed in the same order as the classic deck.
+const LEGACY_CARD_NAMES: [(&str, &[&str]); 3] = [
+    ("RoomCard", &["Kitchen", "Ballroom", "Conservatory", "Dining", "Lounge", "Hall", "Study", "Library", "Billiard"]),
+    ("WeaponCard", &["Rope", "Candlestick", "Knife", "Pipe", "Pistol", "Wrench"]),
+    ("SuspectCard", &["Mustard", "Plum", "Green", "Peacock", "Scarlet", "White"]),
+];
+
+impl Deck {
+    pub fn new(name: String, categories: Vec<Category>) -> Result<Deck> {
+        let deck = Deck { name, categories };
+
+        deck.verify()?;
+
+        Ok(deck)
+    }
+
+    /// The deck of the classic Clue board game
+    pub fn classic() -> Deck {
+        Deck {
+            name: "Classic".to_owned(),
+            categories: vec![
+                Category::new("Rooms", &["Kitchen", "Ballroom", "Conservatory", "Dining Room", "Lounge",
+                    "Hall", "Study", "Library", "Billiard Room"]),
+                Category::new("Weapons", &["Rope", "Candlestick", "Knife", "Lead Pipe", "Pistol", "Wrench"]),
+                Category::new("Suspects", &["Colonel Mustard", "Professor Plum", "Mr. Green", "Mrs. Peacock",
+                    "Miss Scarlet", "Mrs. White"]),
+            ],
+        }
+    }
+
+    /// The deck of UK Cluedo, which names some cards differently
+    pub fn uk() -> Deck {
+        Deck {
+            name: "UK Cluedo".to_owned(),
+            categories: vec![
+                Category::new("Rooms", &["Kitchen", "Ballroom", "Conservatory", "Dining Room", "Lounge",
+                    "Hall", "Study", "Library", "Billiard Room"]),
+                Category::new("Weapons", &["Rope", "Candlestick", "Dagger", "Lead Piping", "Revolver", "Spanner"]),
+                Category::new("Suspects", &["Colonel Mustard", "Professor Plum", "Reverend Green", "Mrs. Peacock",
+                    "Miss Scarlett", "Mrs. White"]),
+            ],
+        }
+    }
+
+    /// The deck of the 2016 edition of Clue, where Dr. Orchid replaces Mrs. White
+    pub fn edition_2016() -> Deck {
+        Deck {
+            name: "2016".to_owned(),
+            categories: vec![
+                Category::new("Rooms", &["Kitchen", "Ballroom", "Conservatory", "Dining Room", "Lounge",
+                    "Hall", "Study", "Library", "Billiard Room"]),
+                Category::new("Weapons", &["Rope", "Candlestick", "Knife", "Lead Pipe", "Revolver", "Wrench"]),
+                Category::new("Suspects", &["Colonel Mustard", "Professor Plum", "Mr. Green", "Mrs. Peacock",
+                    "Miss Scarlet", "Dr. Orchid"]),
+            ],
+        }
+    }
+
+    /// The deck of Clue Master Detective, with 30 cards
+    pub fn master_detective() -> Deck {
+        Deck {
+            name: "Master Detective".to_owned(),
+            categories: vec![
+                Category::new("Rooms", &["Carriage House", "Kitchen", "Trophy Room", "Dining Room", "Drawing Room",
+                    "Gazebo", "Courtyard", "Fountain", "Library", "Billiard Room", "Studio", "Conservatory"]),
+                Category::new("Weapons", &["Rope", "Candlestick", "Knife", "Lead Pipe", "Revolver", "Wrench",
+                    "Horseshoe", "Poison"]),
+                Category::new("Suspects", &["Colonel Mustard", "Professor Plum", "Mr. Green", "Mrs. Peacock",
+                    "Miss Scarlet", "Mrs. White", "Madame Rose", "Sergeant Gray", "Monsieur Brunette", "Miss Peach"]),
+            ],
+        }
+    }
+
+    /// The deck of a built in edition, named as in EDITIONS
+    pub fn from_edition(edition: &str) -> Result<Deck> {
+        match edition {
+            "classic" => Ok(Deck::classic()),
+            "uk" => Ok(Deck::uk()),
+            "2016" => Ok(Deck::edition_2016()),
+            "master" => Ok(Deck::master_detective()),
+            _ => bail!(ErrorKind::UnknownEdition(edition.to_owned(), EDITIONS.join(", "))),
+        }
+    }
+
+    /// Reads a deck from a JSON file, like
+    /// `{"name": "House Rules", "categories": [{"name": "Rooms", "cards": ["Kitchen", ...]}, ...]}`
+    pub fn read_from_file(path: &str) -> Result<Deck> {
+        let mut file = File::open(path)?;
+        let mut json_buf: String = String::new();
+        file.read_to_string(&mut json_buf)?;
+        let deserialized: Deck = serde_json::from_str(&json_buf)?;
+
+        deserialized.verify()?;
+
+        Ok(deserialized)
+    }
+
+    /// Ensures every category has a card, card names aren't repeated,
+    /// and the deck fits in a CardSet.
+    pub fn verify(&self) -> Result<()> {
+        if self.categories.is_empty() {
+            bail!(ErrorKind::InvalidDeck(self.name.clone(), "it has no categories".to_owned()));
+        }
+
+        if let Some(category) = self.categories.iter().find(|category| category.cards.is_empty()) {
+            bail!(ErrorKind::InvalidDeck(self.name.clone(), format!("{} has no cards", category.name)));
+        }
+
+        if self.get_total_cards() > CardSet::MAX_CARDS {
+            bail!(ErrorKind::InvalidDeck(self.name.clone(),
+                format!("it has {} cards, while the limit is {}", self.get_total_cards(), CardSet::MAX_CARDS)));
+        }
+
+        let mut names: Vec<String> = self.categories.iter()
+            .flat_map(|category| category.cards.iter())
+            .map(|name| simplify_name(name))
+            .collect();
+        names.sort();
+
+        if let Some(repeated) = names.windows(2).find(|pair| pair[0] == pair[1]) {
+            bail!(ErrorKind::InvalidDeck(self.name.clone(), format!("\"{}\" is listed twice", repeated[0])));
+        }
+
+        Ok(())
+    }
+
+    pub fn get_total_cards(&self) -> usize {
+        self.categories.iter().map(|category| category.cards.len()).sum()
+    }
+
+    /// The envelope holds one card of each category
+    pub fn get_number_of_categories(&self) -> usize {
+        self.categories.len()
+    }
+
+    /// Number of cards dealt out, or left face up, rather than put in the envelope
+    pub fn get_number_of_cards_outside_envelope(&self) -> usize {
+        self.get_total_cards() - self.get_number_of_categories()
+    }
+
+    /// Number of cards each player gets when every card is dealt out, for
+    /// players in seat order.
+    ///
+    /// Dealing starts with the player after the dealer in the direction of
+    /// play, so when the cards don't split evenly those first players get
+    /// one card more.
+    pub fn get_dealt_hand_sizes(&self, number_of_players: usize, dealer_index: usize,
+        play_direction: PlayDirection) -> Vec<usize> {
+
+        let number_of_cards = self.get_number_of_cards_outside_envelope();
+
+        (0..number_of_players)
+            .map(|player_idx| {
+                let deal_position = match play_direction {
+                    PlayDirection::Clockwise => (player_idx + number_of_players - dealer_index - 1) % number_of_players,
+                    PlayDirection::Counterclockwise => (dealer_index + number_of_players - player_idx - 1) % number_of_players,
+                };
+
+                number_of_cards / number_of_players + usize::from(deal_position < number_of_cards % number_of_players)
+            })
+            .collect()
+    }
+
+    pub fn get_all_cards(&self) -> CardSet {
+        (0..self.get_total_cards()).map(Card::from_index).collect()
+    }
+
+    pub fn get_all_cards_in_category(&self, category_index: usize) -> CardSet {
+        let first_index = self.get_first_index_of_category(category_index);
+
+        (first_index..first_index + self.categories[category_index].cards.len())
+            .map(Card::from_index)
+            .collect()
+    }
+
+    pub fn get_category_index(&self, card: &Card) -> usize {
+        let mut first_index = 0;
+
+        for (category_index, category) in self.categories.iter().enumerate() {
+            if card.get_index() < first_index + category.cards.len() {
+                return category_index;
+            }
+
+            first_index += category.cards.len();
+        }
+
+        panic!("card {} is not in the {} deck", card.get_index(), self.name);
+    }
+
+    pub fn get_card_name(&self, card: &Card) -> &str {
+        let category_index = self.get_category_index(card);
+
+        &self.categories[category_index].cards[card.get_index() - self.get_first_index_of_category(category_index)]
+    }
+
+    /// The card named, like "Lead Pipe".
+    ///
+    /// Ignores case, spaces and punctuation, and also takes one word of
+    /// the name, like "pipe", if only one card has that word.
+    pub fn find_card(&self, name: &str) -> Option<Card> {
+        let name = simplify_name(name);
+
+        let all_cards = self.get_all_cards();
+
+        if let Some(card) = all_cards.iter().find(|card| simplify_name(self.get_card_name(card)) == name) {
+            return Some(card);
+        }
+
+        let matching_cards: Vec<Card> = all_cards.iter()
+            .filter(|card| self.get_card_name(card).split_whitespace().any(|word| simplify_name(word) == name))
+            .collect();
+
+        match matching_cards[..] {
+            [card] => Some(card),
+            _ => None,
+        }
+    }
+
+    fn get_first_index_of_category(&self, category_index: usize) -> usize {
+        self.categories[..category_index].iter().map(|category| category.cards.len()).sum()
+    }
+}
+
+impl Category {
+    pub fn new(name: &str, cards: &[&str]) -> Category {
+        Category {
+            name: name.to_owned(),
+            cards: cards.iter().map(|card| card.to_string()).collect(),
+        }
+    }
+}
+
+/// A card from a save made before decks, like "Pipe" in the "WeaponCard" category.
+pub(crate) fn get_legacy_card(category_name: &str, card_name: &str) -> Option<Card> {
+    let mut first_index = 0;
+
+    for (legacy_category_name, card_names) in LEGACY_CARD_NAMES {
+        if legacy_category_name == category_name {
+            return card_names.iter()
+                .position(|name| *name == card_name)
+                .map(|position| Card::from_index(first_index + position));
+        }
+
+        first_index += card_names.len();
+    }
+
+    None
+}
+
+fn simplify_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_alphanumeric())
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
+
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::constraint_solver::propagate_state_complete;
+    use crate::simulation::simulate_game_with_deck;
+    use crate::solver::propagate_state;
+
+    use super::*;
+
+    /// A card of the classic deck, by name
+    pub(crate) fn card(name: &str) -> Card {
+        Deck::classic().find_card(name).unwrap()
+    }
+
+    #[test]
+    fn test_deck_find_card() {
+        let deck = Deck::classic();
+
+        let pipe = deck.find_card("Lead Pipe").unwrap();
+        assert_eq!(deck.get_card_name(&pipe), "Lead Pipe");
+        assert_eq!(deck.find_card("pipe"), Some(pipe));
+        assert_eq!(deck.get_category_index(&pipe), 1);
+
+        assert_eq!(deck.find_card("mrs. white"), Some(card("White")));
+        // Both Mrs. Peacock and Mrs. White
+        assert_eq!(deck.find_card("Mrs"), None);
+        assert_eq!(deck.find_card("Garden"), None);
+    }
+
+    #[test]
+    fn test_deck_categories() {
+        let deck = Deck::classic();
+
+        assert_eq!(deck.get_total_cards(), 21);
+        assert_eq!(deck.get_number_of_cards_outside_envelope(), 18);
+
+        let sizes: Vec<usize> = (0..deck.get_number_of_categories())
+            .map(|category_index| deck.get_all_cards_in_category(category_index).len())
+            .collect();
+        assert_eq!(sizes, vec![9, 6, 6]);
+
+        for card in deck.get_all_cards() {
+            let category_index = deck.get_category_index(&card);
+            assert!(deck.get_all_cards_in_category(category_index).contains(&card));
+        }
+    }
+
+    #[test]
+    fn test_deck_get_dealt_hand_sizes() {
+        let deck = Deck::classic();
+
+        assert_eq!(deck.get_dealt_hand_sizes(3, 0, PlayDirection::Clockwise), vec![6, 6, 6]);
+        // 18 cards to 4 players, the two after the dealer get a fifth card
+        assert_eq!(deck.get_dealt_hand_sizes(4, 0, PlayDirection::Clockwise), vec![4, 5, 5, 4]);
+        assert_eq!(deck.get_dealt_hand_sizes(4, 2, PlayDirection::Clockwise), vec![5, 4, 4, 5]);
+        assert_eq!(deck.get_dealt_hand_sizes(5, 4, PlayDirection::Clockwise), vec![4, 4, 4, 3, 3]);
+
+        for number_of_players in 2..=6 {
+            let hand_sizes = deck.get_dealt_hand_sizes(number_of_players, 1, PlayDirection::Clockwise);
+            assert_eq!(hand_sizes.iter().sum::<usize>(), 18);
+        }
+    }
+
+    #[test]
+    fn test_deck_get_dealt_hand_sizes_counterclockwise() {
+        let deck = Deck::classic();
+
+        // Dealing goes to the right, so the two before the dealer in seat order get a fifth card
+        assert_eq!(deck.get_dealt_hand_sizes(4, 0, PlayDirection::Counterclockwise), vec![4, 4, 5, 5]);
+        assert_eq!(deck.get_dealt_hand_sizes(4, 2, PlayDirection::Counterclockwise), vec![5, 5, 4, 4]);
+        assert_eq!(deck.get_dealt_hand_sizes(5, 4, PlayDirection::Counterclockwise), vec![3, 4, 4, 4, 3]);
+
+        for number_of_players in 2..=6 {
+            let hand_sizes = deck.get_dealt_hand_sizes(number_of_players, 1, PlayDirection::Counterclockwise);
+            assert_eq!(hand_sizes.iter().sum::<usize>(), 18);
+        }
+    }
+
+    #[test]
+    fn test_deck_verify() {
+        assert!(Deck::classic().verify().is_ok());
+
+        assert!(Deck::new("Empty".to_owned(), Vec::new()).is_err());
+        assert!(Deck::new("No Weapons".to_owned(), vec![
+            Category::new("Rooms", &["Kitchen"]),
+            Category::new("Weapons", &[]),
+        ]).is_err());
+        assert!(Deck::new("Repeated".to_owned(), vec![
+            Category::new("Rooms", &["Kitchen", "Hall"]),
+            Category::new("Weapons", &["Knife", "kitchen"]),
+        ]).is_err());
+    }
+
+    #[test]
+    fn test_deck_editions() {
+        for edition in EDITIONS {
+            let deck = Deck::from_edition(edition).unwrap();
+            assert!(deck.verify().is_ok(), "{}", edition);
+        }
+
+        let master = Deck::from_edition("master").unwrap();
+        assert_eq!(master.get_total_cards(), 30);
+        assert_eq!(master.get_number_of_cards_outside_envelope(), 27);
+
+        let uk = Deck::from_edition("uk").unwrap();
+        assert_eq!(uk.get_card_name(&card("Green")), "Reverend Green");
+        assert_eq!(uk.get_card_name(&card("Pistol")), "Revolver");
+
+        let edition_2016 = Deck::from_edition("2016").unwrap();
+        assert_eq!(edition_2016.get_card_name(&card("White")), "Dr. Orchid");
+
+        assert!(Deck::from_edition("junior").is_err());
+    }
+
+    #[test]
+    fn test_get_legacy_card() {
+        let deck = Deck::classic();
+
+        for (category_name, card_names) in LEGACY_CARD_NAMES {
+            for card_name in card_names {
+                let card = get_legacy_card(category_name, card_name).unwrap();
+                assert_eq!(deck.find_card(card_name), Some(card), "{}", card_name);
+            }
+        }
+
+        assert_eq!(get_legacy_card("RoomCard", "Pipe"), None);
+    }
+
+    #[test]
+    fn test_master_detective_deals() {
+        for number_of_players in 3..=10 {
+            let mut rules_gs = simulate_game_with_deck(Deck::master_detective(), number_of_players, 40 + number_of_players as u64, 20, false);
+            let mut complete_gs = rules_gs.clone();
+
+            let hand_size = rules_gs.get_number_of_expected_cards_per_hand();
+            assert_eq!(hand_size * number_of_players + rules_gs.public_cards.len(), 27);
+            assert_eq!(rules_gs.player_hands[0].must_have.len(), hand_size);
+            assert!(rules_gs.player_hands[0].is_complete(hand_size, &rules_gs.deck));
+
+            propagate_state(&mut rules_gs).unwrap();
+            propagate_state_complete(&mut complete_gs).unwrap();
+
+            rules_gs.verify_state().unwrap();
+            complete_gs.verify_state().unwrap();
+
+            for holder in rules_gs.get_holders() {
+                let rules_hand = rules_gs.get_hand(holder);
+                let complete_hand = complete_gs.get_hand(holder);
+
+                assert!(rules_hand.must_have.is_subset(&complete_hand.must_have), "{} players", number_of_players);
+                assert!(rules_hand.must_not_have.is_subset(&complete_hand.must_not_have), "{} players", number_of_players);
+            }
+        }
+    }
+
+    #[test]
+    fn test_uneven_deals() {
+        for (number_of_players, seed) in [(4, 21), (5, 22), (5, 23)] {
+            let gs = simulate_game_with_deck(Deck::classic(), number_of_players, seed, 15, true);
+
+            assert!(gs.public_cards.is_empty());
+            assert_ne!(gs.get_hand_size(0), gs.get_hand_size(number_of_players - 1));
+            gs.verify_state().unwrap();
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use std::{collections::HashSet, fmt};
+
+use crate::{cards::Card, holder::Holder, game_state::GameState};
+
+/// Something we know about where a card is.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct Fact {
+    pub holder: Holder,
+    pub card: Card,
+
+    /// Whether the holder has the card, or doesn't have it
+    pub has: bool,
+}
+
+impl Fact {
+    pub fn new(holder: Holder, card: Card, has: bool) -> Fact {
+        Fact { holder, card, has }
+    }
+}
+
+/// The rule used to deduce a fact.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Rule {
+    ShowedCard,
+    CouldNotRespond,
+    PublicCard,
+    AnotherHolderHasIt,
+    OnlyPlaceLeft,
+    OnlyRemainingOption,
+    EnvelopeHasCategory,
+    LastOfCategory,
+    HandIsFull,
+    FillsRestOfHand,
+    GroupsFillHand,
+    OpenSlotsFilled,
+    WrongFinalAccusation,
+    Observed,
+    EveryConsistentDeal,
+    EveryDealAllowingMistakes,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rule::ShowedCard => write!(f, "showed card"),
+            Rule::CouldNotRespond => write!(f, "could not respond"),
+            Rule::PublicCard => write!(f, "card is face up"),
+            Rule::AnotherHolderHasIt => write!(f, "another player has it"),
+            Rule::OnlyPlaceLeft => write!(f, "nobody else can have it"),
+            Rule::OnlyRemainingOption => write!(f, "only remaining option"),
+            Rule::EnvelopeHasCategory => write!(f, "envelope already has a card of this kind"),
+            Rule::LastOfCategory => write!(f, "last card of its kind that can be in the envelope"),
+            Rule::HandIsFull => write!(f, "hand is full"),
+            Rule::FillsRestOfHand => write!(f, "only cards left fill the rest of the hand"),
+            Rule::GroupsFillHand => write!(f, "cards they must have one of fill the rest of the hand"),
+            Rule::OpenSlotsFilled => write!(f, "other cards fill every open slot"),
+            Rule::WrongFinalAccusation => write!(f, "a wrong final accusation named it with the envelope's other cards"),
+            Rule::Observed => write!(f, "seen outside of an accusation"),
+            Rule::EveryConsistentDeal => write!(f, "true in every possible deal"),
+            Rule::EveryDealAllowingMistakes => write!(f, "true in every possible deal, even if some accusations are wrong"),
+        }
+    }
+}
+
+/// Why we know a fact.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Deduction {
+    pub fact: Fact,
+    pub rule: Rule,
+
+    /// Index of the accusation the fact comes from, if any
+    pub accusation_index: Option<usize>,
+
+    /// Facts this was deduced from
+    pub premises: Vec<Fact>,
+}
+
+impl Deduction {
+    pub fn new(fact: Fact, rule: Rule) -> Deduction {
+        Deduction { fact, rule, accusation_index: None, premises: Vec::new() }
+    }
+
+    pub fn from_accusation(mut self, accusation_index: usize) -> Deduction {
+        self.accusation_index = Some(accusation_index);
+        self
+    }
+
+    pub fn because(mut self, premises: Vec<Fact>) -> Deduction {
+        self.premises = premises;
+        self
+    }
+}
+
+/// Every fact needed to deduce the given fact, with each fact
+/// coming after the facts it was deduced from, ending with the fact itself.
+pub fn get_reasoning_chain(gs: &GameState, fact: &Fact) -> Vec<Fact> {
+    let mut chain: Vec<Fact> = Vec::new();
+    let mut visited: HashSet<Fact> = HashSet::new();
+
+    add_to_reasoning_chain(gs, fact, &mut chain, &mut visited);
+
+    chain
+}
+
+fn add_to_reasoning_chain(gs: &GameState, fact: &Fact, chain: &mut Vec<Fact>, visited: &mut HashSet<Fact>) {
+    if !visited.insert(fact.clone()) {
+        return;
+    }
+
+    if let Some(deduction) = gs.get_deduction(fact) {
+        for premise in deduction.premises.iter() {
+            add_to_reasoning_chain(gs, premise, chain, visited);
+        }
+    }
+
+    chain.push(fact.clone());
+}
@@ -1,19 +1,50 @@
-use std::{io::{Write, Read}, fs::File, collections::HashSet};
+use std::{io::{Write, Read}, fs::File};
 
 use error_chain::bail;
 
-use crate::{player_hand::*, accusation::Accusation, cards::Card};
+use crate::{player_hand::*, accusation::{Accusation, FinalAccusation}, cards::Card, card_set::CardSet, deck::Deck,
+    holder::Holder, deduction::{Deduction, Fact}, audit::Reveal, accusation::Response, observation::Observation,
+    rules::{GameRules, PlayDirection, ResponseRule}, solver::get_responding_players};
 
 use crate::errors::*;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
 pub struct GameState {
-    pub public_cards: HashSet<Card>,
+    /// Saves from before decks are always the classic deck
+    #[serde(default = "Deck::classic")]
+    pub deck: Deck,
+
+    pub public_cards: CardSet,
 
     pub player_hands: Vec<PlayerHand>,
-    pub self_index: usize,
+
+    /// Your seat, None when watching the game rather than playing
+    pub self_index: Option<usize>,
+
+    #[serde(default = "PlayerHand::new_envelope")]
+    pub envelope: PlayerHand,
 
     pub accusations: Vec<Accusation>,
+
+    /// Final accusations that were wrong, each eliminating its accuser
+    #[serde(default)]
+    pub final_accusations: Vec<FinalAccusation>,
+
+    /// What was seen outside of accusations
+    #[serde(default)]
+    pub observations: Vec<Observation>,
+
+    /// Why we know each fact we've deduced
+    #[serde(default)]
+    pub deductions: Vec<Deduction>,
+
+    /// House rules the game is played with
+    #[serde(default)]
+    pub rules: GameRules,
+
+    /// Where the cards really were, once the game is over
+    #[serde(default)]
+    pub reveal: Option<Reveal>,
 }
 
 impl GameState {
@@ -24,12 +55,12 @@ impl GameState {
     /// 
     /// Starting player dictates who is starting, 0 being you, 1 being the first listed
     /// in the other_player_names, and so on.
-    pub fn new_game_state(self_hand: PlayerHand, other_player_hands: Vec<PlayerHand>, 
-        starting_player: usize, public_cards: HashSet<Card>) -> GameState {
+    pub fn new_game_state(deck: Deck, self_hand: PlayerHand, other_player_hands: Vec<PlayerHand>, 
+        starting_player: usize, public_cards: CardSet, rules: GameRules) -> GameState {
 
         let mut player_hands: Vec<PlayerHand> = Vec::new();
 
-        let mut self_index: usize = 999;
+        let mut self_index: Option<usize> = None;
 
         // Order the elements correctly other_player_h
         for idx in 0..(other_player_hands.len() + 1) {
@@ -37,7 +68,7 @@ impl GameState {
 
             if ordered_index == 0 {
                 player_hands.push(self_hand.clone());
-                self_index = idx;
+                self_index = Some(idx);
             } else {
                 player_hands.push(
                     other_player_hands.get(ordered_index - 1).unwrap().to_owned()
@@ -46,17 +77,56 @@ impl GameState {
         }
 
         GameState {
+            deck,
+
             public_cards,
 
             player_hands,
             self_index,
 
+            envelope: PlayerHand::new_envelope(),
+
             accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+
+            deductions: Vec::new(),
+
+            rules,
+
+            reveal: None,
         }
 
         
     }
 
+    /// Starts a new game which we're watching rather than playing, so no
+    /// hand is known from the start.
+    /// 
+    /// Player hands must be in order of play, starting with the player who goes first.
+    pub fn new_spectated_game_state(deck: Deck, player_hands: Vec<PlayerHand>, public_cards: CardSet,
+        rules: GameRules) -> GameState {
+
+        GameState {
+            deck,
+            public_cards,
+            player_hands,
+            self_index: None,
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules,
+            reveal: None,
+        }
+    }
+
+    /// Whether the player is you
+    pub fn is_self(&self, player_idx: usize) -> bool {
+        self.self_index == Some(player_idx)
+    }
+
 
     /// Ensures the state makes sense
     /// 
@@ -66,15 +136,26 @@ impl GameState {
     /// 
     /// There must be no contradictions between must have and must not have.
     /// 
-    /// Self must be complete
+    /// The envelope must be able to hold exactly one card of each category.
+    /// 
+    /// Self must be complete, unless watching the game
     pub fn verify_state(&self) -> Result<()> {
 
         // Check all player indecies are valid
-        if self.self_index >= self.player_hands.len() {
-            bail!(ErrorKind::InvalidPlayerIndex(
-                "self index".to_owned(),
-                self.self_index
-            ))
+        if let Some(self_index) = self.self_index {
+            if self_index >= self.player_hands.len() {
+                bail!(ErrorKind::InvalidPlayerIndex(
+                    "self index".to_owned(),
+                    self_index
+                ))
+            }
+
+            if self.player_hands[self_index].is_dummy() {
+                bail!(ErrorKind::InvalidPlayerIndex(
+                    "self index, which is a dummy hand".to_owned(),
+                    self_index
+                ))
+            }
         }
 
         for acc in self.accusations.iter() {
@@ -86,40 +167,213 @@ impl GameState {
                 ))
             }
 
-            if acc.responding_player_index.is_none() {
-                continue;
+            if self.player_hands[acc.accuser_player_index].is_dummy() {
+                bail!(ErrorKind::DummyHandTakesTurn(
+                    self.player_hands[acc.accuser_player_index].player_name.to_owned()))
+            }
+
+            if let Some(responder) = acc.responding_player_index {
+                if responder >= self.player_hands.len() {
+                    bail!(ErrorKind::InvalidPlayerIndex(
+                        "accusation responding_player_index".to_owned(),
+                        responder
+                    ))
+                }
+
+                if responder == acc.accuser_player_index {
+                    bail!(ErrorKind::InvalidPlayerIndex(
+                        "accusation responding_player_index which is same as accuser_player_index".to_owned(),
+                        responder
+                    ))
+                }
             }
 
-            if acc.responding_player_index.unwrap() >= self.player_hands.len() {
+            for (response_idx, response) in acc.responses.iter().enumerate() {
+                if response.player_index >= self.player_hands.len() {
+                    bail!(ErrorKind::InvalidPlayerIndex(
+                        "accusation response player_index".to_owned(),
+                        response.player_index
+                    ))
+                }
+
+                if response.player_index == acc.accuser_player_index
+                    || acc.responses[..response_idx].iter().any(|earlier| earlier.player_index == response.player_index) {
+                    bail!(ErrorKind::InvalidPlayerIndex(
+                        "accusation response player_index which is the accuser or already responded".to_owned(),
+                        response.player_index
+                    ))
+                }
+            }
+
+            if !acc.possible_responders.is_empty() {
+                for (possible_idx, player_idx) in acc.possible_responders.iter().enumerate() {
+                    if *player_idx >= self.player_hands.len() {
+                        bail!(ErrorKind::InvalidPlayerIndex(
+                            "accusation possible_responders".to_owned(),
+                            *player_idx
+                        ))
+                    }
+
+                    if !self.get_asked_players(acc).contains(player_idx)
+                        || acc.possible_responders[..possible_idx].contains(player_idx) {
+                        bail!(ErrorKind::InvalidResponses(
+                            self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                            format!("{} might have responded, yet wasn't asked, or is listed twice",
+                                self.player_hands[*player_idx].player_name)))
+                    }
+                }
+
+                if acc.responding_player_index.is_some() || acc.card_shown.is_some()
+                    || self.rules.response_rule != ResponseRule::FirstCanShow {
+                    bail!(ErrorKind::InvalidResponses(
+                        self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                        "who responded is both known and missed".to_owned()))
+                }
+
+                // The first player who can show a card ends the asking
+                if let Some(last_asked) = acc.asked_players.as_ref().and_then(|asked_players| asked_players.last()) {
+                    if !acc.possible_responders.contains(last_asked) {
+                        bail!(ErrorKind::InvalidAskingOrder(
+                            self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                            format!("{} was asked, yet only after whoever responded",
+                                self.player_hands[*last_asked].player_name)))
+                    }
+                }
+
+                let all_ruled_out = acc.possible_responders.iter()
+                    .all(|player_idx| acc.accused_cards.iter()
+                        .all(|card| self.player_hands[*player_idx].must_not_have.contains(card)));
+
+                if all_ruled_out {
+                    bail!(ErrorKind::InvalidResponses(
+                        self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                        "none of the players who might have responded can have the cards".to_owned()))
+                }
+            }
+
+            // Only one player responds, unless everyone who can must show
+            if self.rules.response_rule == ResponseRule::FirstCanShow && !acc.responses.is_empty() {
+                bail!(ErrorKind::InvalidResponses(
+                    self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                    "several players responded, yet only the first who can shows a card".to_owned()))
+            }
+
+            // Everyone who showed a card is listed in the responses instead
+            if self.rules.response_rule == ResponseRule::EveryoneShows
+                && (acc.responding_player_index.is_some() || acc.card_shown.is_some()) {
+                bail!(ErrorKind::InvalidResponses(
+                    self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                    "a single player responded, yet everyone who can shows a card".to_owned()))
+            }
+
+            if let Some(asked_players) = &acc.asked_players {
+                for (asked_idx, player_idx) in asked_players.iter().enumerate() {
+                    if *player_idx >= self.player_hands.len() {
+                        bail!(ErrorKind::InvalidPlayerIndex(
+                            "accusation asked_players".to_owned(),
+                            *player_idx
+                        ))
+                    }
+
+                    if *player_idx == acc.accuser_player_index || asked_players[..asked_idx].contains(player_idx) {
+                        bail!(ErrorKind::InvalidAskingOrder(
+                            self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                            format!("{} is asked more than once, or asked their own accusation",
+                                self.player_hands[*player_idx].player_name)))
+                    }
+                }
+
+                // The first player who can show a card ends the asking
+                if let Some(responder) = acc.responding_player_index {
+                    if asked_players.last() != Some(&responder) {
+                        bail!(ErrorKind::InvalidAskingOrder(
+                            self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                            format!("{} responded, yet wasn't the last player asked",
+                                self.player_hands[responder].player_name)))
+                    }
+                }
+
+                if let Some(response) = acc.responses.iter().find(|response| !asked_players.contains(&response.player_index)) {
+                    bail!(ErrorKind::InvalidAskingOrder(
+                        self.player_hands[acc.accuser_player_index].player_name.to_owned(),
+                        format!("{} responded, yet wasn't asked",
+                            self.player_hands[response.player_index].player_name)))
+                }
+            }
+
+        }
+
+        for final_accusation in self.final_accusations.iter() {
+            if final_accusation.accuser_player_index >= self.player_hands.len() {
                 bail!(ErrorKind::InvalidPlayerIndex(
-                    "accusation responding_player_index".to_owned(),
-                    acc.responding_player_index.unwrap()
+                    "final accusation accuser_player_index".to_owned(),
+                    final_accusation.accuser_player_index
                 ))
             }
 
-            if acc.responding_player_index.unwrap() == acc.accuser_player_index {
+            if self.player_hands[final_accusation.accuser_player_index].is_dummy() {
+                bail!(ErrorKind::DummyHandTakesTurn(
+                    self.player_hands[final_accusation.accuser_player_index].player_name.to_owned()))
+            }
+
+            // Wrong, so the envelope can't have every card named
+            if final_accusation.accused_cards.iter().all(|card| self.envelope.must_have.contains(card)) {
+                bail!(ErrorKind::FinalAccusationContradiction(
+                    self.player_hands[final_accusation.accuser_player_index].player_name.to_owned()))
+            }
+        }
+
+        for observation in self.observations.iter() {
+            if observation.player_index >= self.player_hands.len() {
                 bail!(ErrorKind::InvalidPlayerIndex(
-                    "accusation responding_player_index which is same as accuser_player_index".to_owned(),
-                    acc.responding_player_index.unwrap()
+                    "observation player_index".to_owned(),
+                    observation.player_index
                 ))
             }
+
+            let player = &self.player_hands[observation.player_index];
+
+            let is_contradicted = if observation.has {
+                player.must_not_have.contains(&observation.card) || self.public_cards.contains(&observation.card)
+            } else {
+                player.must_have.contains(&observation.card)
+            };
+
+            let is_seen_both_ways = self.observations.iter().any(|other| other.player_index == observation.player_index
+                && other.card == observation.card && other.has != observation.has);
+
+            if is_contradicted || is_seen_both_ways {
+                bail!(ErrorKind::PlayerCardContradiction(
+                    player.player_name.to_owned(),
+                    self.get_card_name(&observation.card),
+                    "it was seen, yet is known otherwise".to_owned()
+                ));
+            }
         }
 
         // If a card is shown, then the responding player must be Some()
         for acc in self.accusations.iter() {
-            if acc.card_shown.is_some() && acc.responding_player_index.is_none() {
+            if let (Some(card_shown), None) = (&acc.card_shown, acc.responding_player_index) {
                 bail!(ErrorKind::AccusationContradiction(
                     self.player_hands[acc.accuser_player_index].player_name.to_owned(), 
-                    acc.card_shown.as_ref().unwrap().to_string()))
+                    self.get_card_name(card_shown)))
             }   
         }
 
 
-        let number_of_cards_expected = self.get_number_of_expected_cards_per_hand();
+        let number_of_dealt_cards: usize = (0..self.player_hands.len())
+            .map(|player_idx| self.get_hand_size(player_idx))
+            .sum();
+
+        if number_of_dealt_cards + self.public_cards.len() != self.deck.get_number_of_cards_outside_envelope() {
+            bail!(ErrorKind::InvalidHandSizes(format!("{} cards are dealt and {} are face up, out of {}",
+                number_of_dealt_cards, self.public_cards.len(), self.deck.get_number_of_cards_outside_envelope())));
+        }
 
-        let mut already_must_have_cards: HashSet<Card> = HashSet::new();
+        let mut already_must_have_cards: CardSet = CardSet::new();
 
-        for player in self.player_hands.iter() {
+        for (player_idx, player) in self.player_hands.iter().enumerate() {
+            let number_of_cards_expected = self.get_hand_size(player_idx);
 
             if !already_must_have_cards.is_disjoint(&player.must_have) {
                 // Overlapping, Contradiction since two different players can't
@@ -127,15 +381,13 @@ impl GameState {
                 let intersection = already_must_have_cards.intersection(&player.must_have);
                 bail!(ErrorKind::PlayerCardContradiction(
                     player.player_name.to_owned(),
-                    intersection.into_iter().next().unwrap().clone(),
+                    self.get_card_name(&intersection.iter().next().unwrap()),
                     "player must have the same card as another player".to_owned()
                 ));
             }
 
             // Update the cards we've already checked against
-            for card in &player.must_have {
-                already_must_have_cards.insert(card.clone());
-            }
+            already_must_have_cards |= &player.must_have;
 
             if player.must_have.len() > number_of_cards_expected {
                 bail!(ErrorKind::PlayerHasInvalidCardNumber(
@@ -153,18 +405,30 @@ impl GameState {
 
                 bail!(ErrorKind::PlayerCardContradiction(
                     player.player_name.to_owned(),
-                    intersection.into_iter().next().unwrap().clone(),
+                    self.get_card_name(&intersection.iter().next().unwrap()),
                     "player has card in both must have and must not have".to_owned()
                 ));
             }
 
-            if player.must_not_have.len() > (Card::get_total_cards() - number_of_cards_expected) {
+            for cards in player.must_have_one_of.iter() {
+                if cards.is_subset(&player.must_not_have) {
+                    let mut card_names: Vec<String> = cards.iter().map(|card| self.get_card_name(&card)).collect();
+                    card_names.sort();
+
+                    bail!(ErrorKind::PlayerMustHaveOneOfContradiction(
+                        player.player_name.to_owned(),
+                        card_names.join(", ")
+                    ));
+                }
+            }
+
+            if player.must_not_have.len() > (self.deck.get_total_cards() - number_of_cards_expected) {
                 // Can't have more cards then possible
                 bail!(ErrorKind::PlayerHasInvalidCardNumber(
                     player.player_name.clone(),
                     player.must_not_have.len(),
                     "Must not Have Cards".to_owned(), 
-                    Card::get_total_cards() - number_of_cards_expected)
+                    self.deck.get_total_cards() - number_of_cards_expected)
                 );
             }
 
@@ -174,36 +438,86 @@ impl GameState {
                 let public_card_intersection = self.public_cards.intersection(&player.must_have);
                 bail!(ErrorKind::PlayerHasPublicCard(
                     player.player_name.clone(), 
-                    public_card_intersection.into_iter().next().unwrap().clone()));
+                    self.get_card_name(&public_card_intersection.iter().next().unwrap())));
             }
         }
 
-        let self_hand = &self.player_hands[self.self_index];
+        // The envelope holds exactly one card of each category
+        let envelope = &self.envelope;
+
+        if !already_must_have_cards.is_disjoint(&envelope.must_have) {
+            let intersection = already_must_have_cards.intersection(&envelope.must_have);
+            bail!(ErrorKind::PlayerCardContradiction(
+                envelope.player_name.to_owned(),
+                self.get_card_name(&intersection.iter().next().unwrap()),
+                "envelope must have the same card as a player".to_owned()
+            ));
+        }
+
+        if !envelope.must_have.is_disjoint(&envelope.must_not_have) {
+            let intersection = envelope.must_have.intersection(&envelope.must_not_have);
+            bail!(ErrorKind::PlayerCardContradiction(
+                envelope.player_name.to_owned(),
+                self.get_card_name(&intersection.iter().next().unwrap()),
+                "envelope has card in both must have and must not have".to_owned()
+            ));
+        }
+
+        if !self.public_cards.is_disjoint(&envelope.must_have) {
+            let public_card_intersection = self.public_cards.intersection(&envelope.must_have);
+            bail!(ErrorKind::PlayerHasPublicCard(
+                envelope.player_name.clone(), 
+                self.get_card_name(&public_card_intersection.iter().next().unwrap())));
+        }
+
+        for category_index in 0..self.deck.get_number_of_categories() {
+            let category_cards = self.deck.get_all_cards_in_category(category_index);
+
+            let number_of_must_haves = category_cards.intersection(&envelope.must_have).len();
+            if number_of_must_haves > 1 {
+                bail!(ErrorKind::PlayerHasInvalidCardNumber(
+                    envelope.player_name.clone(),
+                    number_of_must_haves,
+                    "Must Have Cards in one category".to_owned(),
+                    1)
+                );
+            }
+
+            if category_cards.is_subset(&envelope.must_not_have) {
+                bail!(ErrorKind::PlayerHasInvalidCardNumber(
+                    envelope.player_name.clone(),
+                    category_cards.len(),
+                    "Must not Have Cards in one category".to_owned(),
+                    category_cards.len() - 1)
+                );
+            }
+        }
 
         // If someone has a card they showed, then that must not be in their must not have.
         for accusation in &self.accusations {
-            if accusation.card_shown.is_some() {
-                let responding_player = 
-                    self.player_hands.get(accusation.responding_player_index.unwrap()).unwrap();
+            for response in self.get_responses(accusation) {
+                let card_shown = match &response.card_shown {
+                    Some(card_shown) => card_shown,
+                    None => continue,
+                };
 
-                if responding_player.must_not_have.contains(accusation.card_shown.as_ref().unwrap()) {
+                let responding_player = &self.player_hands[response.player_index];
 
-                    let card = responding_player
-                        .must_not_have.iter()
-                        .find(|card| &(*card).clone() == accusation.card_shown.as_ref().unwrap())
-                        .unwrap();
+                if responding_player.must_not_have.contains(card_shown) {
 
                     bail!(ErrorKind::PlayerCardContradiction(
                         responding_player.player_name.to_owned(),
-                        card.clone(),
+                        self.get_card_name(card_shown),
                         "player has showed a card they must not have".to_owned()
                     ));
                 }
             }
         }
 
-        if !self_hand.is_complete(number_of_cards_expected) {
-            bail!(ErrorKind::SelfIsNotComplete);
+        if let Some(self_index) = self.self_index {
+            if !self.player_hands[self_index].is_complete(self.get_hand_size(self_index), &self.deck) {
+                bail!(ErrorKind::SelfIsNotComplete);
+            }
         }
 
         Ok(())
@@ -213,8 +527,204 @@ impl GameState {
         self.accusations.push(accusation);
     }
 
+    /// Players who showed a card for the accusation, with the card if we saw it
+    pub fn get_responses(&self, acc: &Accusation) -> Vec<Response> {
+        match self.rules.response_rule {
+            ResponseRule::FirstCanShow => acc.responding_player_index
+                .map(|player_index| Response { player_index, card_shown: acc.card_shown })
+                .into_iter()
+                .collect(),
+            ResponseRule::EveryoneShows => acc.responses.clone(),
+        }
+    }
+
+    /// Players who were asked to respond to the accusation, in the order they were asked
+    pub fn get_asked_players(&self, acc: &Accusation) -> Vec<usize> {
+        if let Some(asked_players) = &acc.asked_players {
+            return asked_players.clone();
+        }
+
+        let mut asked_players = get_responding_players(acc.accuser_player_index, None, self.player_hands.len());
+
+        if self.rules.play_direction == PlayDirection::Counterclockwise {
+            asked_players.reverse();
+        }
+
+        asked_players
+    }
+
+    /// Players who were asked about the accusation, yet had none of the cards
+    pub fn get_players_without_cards(&self, acc: &Accusation) -> Vec<usize> {
+        let asked_players = self.get_asked_players(acc);
+
+        match self.rules.response_rule {
+            ResponseRule::FirstCanShow => asked_players.into_iter()
+                .take_while(|player_idx| Some(*player_idx) != acc.responding_player_index
+                    && !acc.possible_responders.contains(player_idx))
+                .collect(),
+            ResponseRule::EveryoneShows => asked_players.into_iter()
+                .filter(|player_idx| !acc.responses.iter().any(|response| response.player_index == *player_idx))
+                .collect(),
+        }
+    }
+
+    /// When we missed who responded to the accusation, clauses which at least
+    /// one fact of each is true, saying the first player asked who had one of
+    /// the cards was one of the possible responders. Empty if we know who
+    /// responded.
+    pub fn get_possible_responder_clauses(&self, acc: &Accusation) -> Vec<Vec<Fact>> {
+        let mut clauses: Vec<Vec<Fact>> = Vec::new();
+
+        if acc.possible_responders.is_empty() {
+            return clauses;
+        }
+
+        let asked_players = self.get_asked_players(acc);
+
+        let last_possible_position = match asked_players.iter()
+            .rposition(|player_idx| acc.possible_responders.contains(player_idx)) {
+            Some(position) => position,
+            None => return vec![Vec::new()],
+        };
+
+        let has_any_card = |player_idx: usize| acc.accused_cards.iter()
+            .map(move |card| Fact::new(Holder::Player(player_idx), *card, true));
+
+        // Someone asked up to the last possible responder had one of the cards
+        clauses.push(asked_players[..=last_possible_position].iter()
+            .flat_map(|player_idx| has_any_card(*player_idx))
+            .collect());
+
+        // Anyone else only has one of the cards if someone asked before them did
+        for (position, player_idx) in asked_players[..last_possible_position].iter().enumerate() {
+            if acc.possible_responders.contains(player_idx) {
+                continue;
+            }
+
+            for card in acc.accused_cards.iter() {
+                clauses.push(std::iter::once(Fact::new(Holder::Player(*player_idx), *card, false))
+                    .chain(asked_players[..position].iter().flat_map(|earlier_idx| has_any_card(*earlier_idx)))
+                    .collect());
+            }
+        }
+
+        clauses
+    }
+
+    /// Records a wrong final accusation, which eliminates the accuser
+    pub fn add_final_accusation(&mut self, final_accusation: FinalAccusation) {
+        self.final_accusations.push(final_accusation);
+    }
+
+    /// Records something seen outside of an accusation
+    pub fn add_observation(&mut self, observation: Observation) {
+        self.observations.push(observation);
+    }
+
+    /// Players who made a wrong final accusation, so no longer take turns
+    pub fn get_eliminated_players(&self) -> Vec<usize> {
+        let mut eliminated: Vec<usize> = self.final_accusations.iter()
+            .map(|final_accusation| final_accusation.accuser_player_index)
+            .collect();
+
+        eliminated.sort();
+        eliminated.dedup();
+
+        eliminated
+    }
+
+    /// Players who can't make accusations, because they're out of the
+    /// game or are a dummy hand
+    pub fn get_players_without_turns(&self) -> Vec<usize> {
+        let mut players_without_turns = self.get_eliminated_players();
+
+        players_without_turns.extend((0..self.player_hands.len())
+            .filter(|player_idx| self.player_hands[*player_idx].is_dummy()));
+
+        players_without_turns.sort();
+        players_without_turns.dedup();
+
+        players_without_turns
+    }
+
+    pub fn get_card_name(&self, card: &Card) -> String {
+        self.deck.get_card_name(card).to_owned()
+    }
+
+    pub fn get_holder_name(&self, holder: Holder) -> String {
+        match holder {
+            Holder::Player(idx) => self.player_hands[idx].player_name.clone(),
+            Holder::Envelope => self.envelope.player_name.clone(),
+        }
+    }
+
+    /// Every holder, players first then the envelope
+    pub fn get_holders(&self) -> Vec<Holder> {
+        (0..self.player_hands.len()).map(Holder::Player)
+            .chain(std::iter::once(Holder::Envelope))
+            .collect()
+    }
+
+    pub fn get_hand(&self, holder: Holder) -> &PlayerHand {
+        match holder {
+            Holder::Player(idx) => &self.player_hands[idx],
+            Holder::Envelope => &self.envelope,
+        }
+    }
+
+    pub fn get_hand_mut(&mut self, holder: Holder) -> &mut PlayerHand {
+        match holder {
+            Holder::Player(idx) => &mut self.player_hands[idx],
+            Holder::Envelope => &mut self.envelope,
+        }
+    }
+
+    /// Adds the deduced fact to its holder's hand, remembering why we know it.
+    /// 
+    /// Returns false if the fact was already known, in which case the
+    /// original reason is kept.
+    pub fn add_deduction(&mut self, deduction: Deduction) -> bool {
+        let fact = &deduction.fact;
+        let hand = self.get_hand_mut(fact.holder);
+
+        let is_new = if fact.has {
+            hand.must_have.insert(fact.card)
+        } else {
+            hand.must_not_have.insert(fact.card)
+        };
+
+        if is_new {
+            self.deductions.push(deduction);
+        }
+
+        is_new
+    }
+
+    /// Whether the fact is known, either given or deduced
+    pub fn is_known(&self, fact: &Fact) -> bool {
+        let hand = self.get_hand(fact.holder);
+
+        if fact.has {
+            hand.must_have.contains(&fact.card)
+        } else {
+            hand.must_not_have.contains(&fact.card)
+        }
+    }
+
+    /// Why we know the fact, None if it was given rather than deduced
+    pub fn get_deduction(&self, fact: &Fact) -> Option<&Deduction> {
+        self.deductions.iter().find(|deduction| deduction.fact == *fact)
+    }
+
+    /// Number of cards in the player's hand
+    pub fn get_hand_size(&self, player_idx: usize) -> usize {
+        self.player_hands[player_idx].hand_size
+            .unwrap_or_else(|| self.get_number_of_expected_cards_per_hand())
+    }
+
+    /// Number of cards in each hand when they're dealt evenly, with the rest face up
     pub fn get_number_of_expected_cards_per_hand(&self) -> usize {
-        let number_of_cards_not_in_center = Card::get_total_cards() - 3;
+        let number_of_cards_not_in_center = self.deck.get_number_of_cards_outside_envelope();
 
         (number_of_cards_not_in_center - (number_of_cards_not_in_center % self.player_hands.len())) / self.player_hands.len()
     }
@@ -235,15 +745,120 @@ impl GameState {
         file.read_to_string(&mut json_buf)?;
         let deserialized: GameState = serde_json::from_str(&json_buf)?;
 
+        deserialized.deck.verify()?;
+        deserialized.verify_cards()?;
+
         Ok(deserialized)
     }
+
+    /// Ensures every card in the game is from its deck, and every accusation
+    /// names one card of each category, since saves may be edited by hand.
+    pub fn verify_cards(&self) -> Result<()> {
+        let mut cards: CardSet = self.public_cards;
+
+        for hand in self.player_hands.iter().chain(std::iter::once(&self.envelope)) {
+            cards |= &hand.must_have;
+            cards |= &hand.must_not_have;
+
+            for group in hand.must_have_one_of.iter() {
+                cards |= group;
+            }
+        }
+
+        for acc in self.accusations.iter() {
+            cards.extend(acc.card_shown);
+            cards.extend(acc.responses.iter().filter_map(|response| response.card_shown));
+        }
+
+        cards.extend(self.observations.iter().map(|observation| observation.card));
+
+        for deduction in self.deductions.iter() {
+            cards.insert(deduction.fact.card);
+            cards.extend(deduction.premises.iter().map(|premise| premise.card));
+        }
+
+        if let Some(reveal) = &self.reveal {
+            cards |= &reveal.envelope;
+
+            for hand in reveal.player_hands.iter().flatten() {
+                cards |= hand;
+            }
+        }
+
+        let accused_cards: Vec<&Vec<Card>> = self.accusations.iter().map(|acc| &acc.accused_cards)
+            .chain(self.final_accusations.iter().map(|final_accusation| &final_accusation.accused_cards))
+            .collect();
+
+        for accused_cards in accused_cards.iter() {
+            cards.extend(accused_cards.iter().copied());
+        }
+
+        if let Some(card) = (&cards - &self.deck.get_all_cards()).iter().next() {
+            bail!(ErrorKind::CardsDontMatchDeck(self.deck.name.clone(),
+                format!("card {} is past its last card", card.get_index())));
+        }
+
+        for accused_cards in accused_cards {
+            let is_one_per_category = accused_cards.len() == self.deck.get_number_of_categories()
+                && accused_cards.iter().enumerate().all(|(category_index, card)| self.deck.get_category_index(card) == category_index);
+
+            if !is_one_per_category {
+                let card_names: Vec<String> = accused_cards.iter().map(|card| self.get_card_name(card)).collect();
+
+                bail!(ErrorKind::CardsDontMatchDeck(self.deck.name.clone(),
+                    format!("{} isn't one card of each category", card_names.join(", "))));
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
 
+    use crate::audit::find_wrong_facts;
+    use crate::simulation::{miss_responders, simulate_game};
+    use crate::player_hand::HandKind;
+    use crate::solver::propagate_state;
+
     use super::*;
 
+    #[test]
+    fn test_read_from_file_rejects_cards_not_in_deck() {
+        let mut gs = simulate_game(4, 141, 6);
+        propagate_state(&mut gs).unwrap();
+
+        let path = std::env::temp_dir().join(format!("cluesolver_test_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        gs.save_to_file(path).unwrap();
+        assert!(GameState::read_from_file(path).is_ok());
+
+        // Saved with a smaller deck than its cards need
+        let mut other_deck = gs.clone();
+        other_deck.deck.categories[2].cards.truncate(2);
+        other_deck.save_to_file(path).unwrap();
+        assert!(GameState::read_from_file(path).is_err());
+
+        // Saved with a deck that lists a card twice
+        let mut repeated_card = gs.clone();
+        repeated_card.deck.categories[0].cards[1] = repeated_card.deck.categories[0].cards[0].clone();
+        repeated_card.save_to_file(path).unwrap();
+        assert!(matches!(GameState::read_from_file(path), Err(Error(ErrorKind::InvalidDeck(..), _))));
+
+        // Past the last card any deck can have
+        std::fs::write(path, serde_json::to_string(&gs).unwrap().replacen("\"public_cards\":[", "\"public_cards\":[200,", 1)).unwrap();
+        assert!(GameState::read_from_file(path).is_err());
+
+        std::fs::remove_file(path).unwrap();
+
+        // Names two weapons
+        let mut wrong_accusation = gs.clone();
+        wrong_accusation.accusations[0].accused_cards[0] = wrong_accusation.accusations[0].accused_cards[1];
+        assert!(wrong_accusation.verify_cards().is_err());
+    }
+
     #[test]
     fn test_new_game_state_self_start() {
         let p1 = PlayerHand::new("Dylan".to_owned());
@@ -252,15 +867,15 @@ mod tests {
 
         let other_players = vec![p2.clone(), p3.clone()];
 
-        let gs = GameState::new_game_state(p1.clone(), other_players, 0,
-            HashSet::new());
+        let gs = GameState::new_game_state(Deck::classic(), p1.clone(), other_players, 0,
+            CardSet::new(), GameRules::default());
 
-        for it in gs.player_hands.iter().zip(vec![p1,p2,p3].iter()) {
+        for it in gs.player_hands.iter().zip([p1,p2,p3].iter()) {
             let (e1, e2) = it;
             assert_eq!(*e1, *e2);
         }
 
-        assert_eq!(gs.self_index, 0)
+        assert_eq!(gs.self_index, Some(0))
     }
 
     #[test]
@@ -272,15 +887,15 @@ mod tests {
 
         let other_players = vec![p2.clone(), p3.clone(), p4.clone()];
 
-        let gs = GameState::new_game_state(p1.clone(), other_players, 2,
-            HashSet::new());
+        let gs = GameState::new_game_state(Deck::classic(), p1.clone(), other_players, 2,
+            CardSet::new(), GameRules::default());
 
-        for it in gs.player_hands.iter().zip(vec![p3,p4,p1,p2].iter()) {
+        for it in gs.player_hands.iter().zip([p3,p4,p1,p2].iter()) {
             let (e1, e2) = it;
             assert_eq!(*e1, *e2);
         }
 
-        assert_eq!(gs.self_index, 2)
+        assert_eq!(gs.self_index, Some(2))
     }
 
     #[test]
@@ -292,16 +907,197 @@ mod tests {
 
         let other_players = vec![p2.clone(), p3.clone(), p4.clone()];
 
-        let gs = GameState::new_game_state(p1.clone(), other_players, 3,
-            HashSet::new());
+        let gs = GameState::new_game_state(Deck::classic(), p1.clone(), other_players, 3,
+            CardSet::new(), GameRules::default());
 
-        for it in gs.player_hands.iter().zip(vec![p4, p1, p2, p3].iter()) {
+        for it in gs.player_hands.iter().zip([p4, p1, p2, p3].iter()) {
             let (e1, e2) = it;
             assert_eq!(*e1, *e2);
         }
 
 
-        assert_eq!(gs.self_index, 1)
+        assert_eq!(gs.self_index, Some(1))
+    }
+
+    #[test]
+    fn test_new_spectated_game_state() {
+        let players = vec![
+            PlayerHand::new("Dylan".to_owned()),
+            PlayerHand::new("Alice".to_owned()),
+            PlayerHand::new("Bob".to_owned()),
+        ];
+
+        let gs = GameState::new_spectated_game_state(Deck::classic(), players.clone(), CardSet::new(), 
+            GameRules::default());
+
+        assert_eq!(gs.player_hands, players);
+        assert_eq!(gs.self_index, None);
+        assert!(!gs.is_self(0));
+
+        // Nobody's hand needs to be known
+        gs.verify_state().unwrap();
+    }
+
+    #[test]
+    fn test_get_players_without_cards_follows_asking_order() {
+        let mut gs = simulate_game(5, 60, 0);
+
+        let mut acc = Accusation {
+            accuser_player_index: 1,
+            accused_cards: Vec::new(),
+            responding_player_index: Some(4),
+            card_shown: None,
+            responses: Vec::new(),
+            asked_players: None,
+            possible_responders: Vec::new(),
+        };
+
+        assert_eq!(gs.get_players_without_cards(&acc), vec![2, 3]);
+
+        gs.rules.play_direction = PlayDirection::Counterclockwise;
+        assert_eq!(gs.get_players_without_cards(&acc), vec![0]);
+
+        // Player 2 was away from the table
+        acc.asked_players = Some(vec![3, 0, 4]);
+        assert_eq!(gs.get_players_without_cards(&acc), vec![3, 0]);
+
+        gs.rules.response_rule = ResponseRule::EveryoneShows;
+        acc.responding_player_index = None;
+        acc.responses = vec![Response { player_index: 0, card_shown: None }];
+        assert_eq!(gs.get_players_without_cards(&acc), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_dummy_hands_respond_but_never_accuse() {
+        // Two players, with a dummy hand between them
+        let mut gs = simulate_game(3, 62, 9);
+        gs.player_hands[1].kind = HandKind::Dummy;
+
+        assert_eq!(gs.get_players_without_turns(), vec![1]);
+        assert!(gs.verify_state().is_err());
+
+        gs.accusations.retain(|acc| acc.accuser_player_index != 1);
+        gs.verify_state().unwrap();
+
+        let mut propagated_gs = gs.clone();
+        propagate_state(&mut propagated_gs).unwrap();
+
+        // The dummy hand still answers accusations
+        let reveal = gs.reveal.clone().unwrap();
+        assert!(find_wrong_facts(&propagated_gs, &reveal).is_empty());
+        assert!(propagated_gs.deductions.iter().any(|deduction| deduction.fact.holder == Holder::Player(1)));
+
+        gs.self_index = Some(1);
+        assert!(gs.verify_state().is_err());
     }
-    
-}
\ No newline at end of file
+
+    #[test]
+    fn test_verify_state_rejects_impossible_asking_orders() {
+        let gs = simulate_game(4, 61, 6);
+        gs.verify_state().unwrap();
+
+        let acc_idx = gs.accusations.iter().position(|acc| acc.responding_player_index.is_some()).unwrap();
+        let acc = &gs.accusations[acc_idx];
+        let responder = acc.responding_player_index.unwrap();
+        let others: Vec<usize> = (0..4)
+            .filter(|player_idx| *player_idx != acc.accuser_player_index && *player_idx != responder)
+            .collect();
+
+        let with_asked_players = |asked_players: Vec<usize>| {
+            let mut gs = gs.clone();
+            gs.accusations[acc_idx].asked_players = Some(asked_players);
+            gs.verify_state()
+        };
+
+        // Skipping someone is fine
+        assert!(with_asked_players(vec![responder]).is_ok());
+
+        // Asked twice, asking the accuser, or asking after someone showed a card
+        assert!(with_asked_players(vec![others[0], others[0], responder]).is_err());
+        assert!(with_asked_players(vec![acc.accuser_player_index, responder]).is_err());
+        assert!(with_asked_players(vec![responder, others[0]]).is_err());
+        assert!(with_asked_players(vec![others[0], 4]).is_err());
+
+        // Responding from past the last seat
+        let mut missing_responder = gs.clone();
+        missing_responder.accusations[acc_idx].asked_players = Some(vec![others[0]]);
+        missing_responder.accusations[acc_idx].responding_player_index = Some(4);
+        assert!(matches!(missing_responder.verify_state(), Err(Error(ErrorKind::InvalidPlayerIndex(..), _))));
+    }
+
+    #[test]
+    fn test_verify_state_rejects_responses_the_rule_ignores() {
+        let gs = simulate_game(4, 63, 6);
+        gs.verify_state().unwrap();
+
+        let acc_idx = gs.accusations.iter().position(|acc| acc.responding_player_index.is_some()).unwrap();
+        let acc = gs.accusations[acc_idx].clone();
+        let response = Response { player_index: acc.responding_player_index.unwrap(), card_shown: acc.card_shown };
+
+        // Only the first who can shows a card, so there's no list of responses
+        let mut listed_responses = gs.clone();
+        listed_responses.accusations[acc_idx].responses = vec![response];
+        assert!(listed_responses.verify_state().is_err());
+
+        let mut everyone_shows = gs.clone();
+        everyone_shows.rules.response_rule = ResponseRule::EveryoneShows;
+
+        for acc in everyone_shows.accusations.iter_mut() {
+            acc.responses = acc.responding_player_index
+                .map(|player_index| Response { player_index, card_shown: acc.card_shown })
+                .into_iter()
+                .collect();
+            acc.responding_player_index = None;
+            acc.card_shown = None;
+        }
+
+        everyone_shows.verify_state().unwrap();
+
+        // Everyone who can shows a card, so there's no single responder
+        let mut single_responder = everyone_shows.clone();
+        single_responder.accusations[acc_idx].responding_player_index = Some(response.player_index);
+        assert!(single_responder.verify_state().is_err());
+
+        let mut single_card_shown = everyone_shows.clone();
+        single_card_shown.accusations[acc_idx].card_shown = Some(acc.accused_cards[0]);
+        assert!(single_card_shown.verify_state().is_err());
+    }
+
+    #[test]
+    fn test_verify_state_rejects_missed_responders_the_rule_ignores() {
+        let mut gs = simulate_game(5, 64, 8);
+        miss_responders(&mut gs);
+        gs.verify_state().unwrap();
+
+        // Missed on a turn where players were asked after both who might have responded
+        let last_possible_position = |acc: &Accusation| gs.get_asked_players(acc).iter()
+            .rposition(|player_idx| acc.possible_responders.contains(player_idx));
+
+        let acc_idx = gs.accusations.iter()
+            .position(|acc| acc.possible_responders.len() == 2
+                && last_possible_position(acc).is_some_and(|position| position + 1 < gs.get_asked_players(acc).len()))
+            .unwrap();
+        let acc = gs.accusations[acc_idx].clone();
+        let asked_players = gs.get_asked_players(&acc);
+        let last_possible_position = last_possible_position(&acc).unwrap();
+
+        let with_change = |change: &dyn Fn(&mut GameState)| {
+            let mut gs = gs.clone();
+            change(&mut gs);
+            gs.verify_state()
+        };
+
+        // Who responded is either known or missed, not both
+        assert!(with_change(&|gs| gs.accusations[acc_idx].responding_player_index = Some(acc.possible_responders[0])).is_err());
+        assert!(with_change(&|gs| gs.accusations[acc_idx].card_shown = Some(acc.accused_cards[0])).is_err());
+
+        // Everyone who can shows a card, so the responses are listed instead
+        assert!(with_change(&|gs| gs.rules.response_rule = ResponseRule::EveryoneShows).is_err());
+
+        // Asking ends at whoever responded
+        assert!(with_change(&|gs| gs.accusations[acc_idx].asked_players =
+            Some(asked_players[..=last_possible_position].to_vec())).is_ok());
+
+        assert!(with_change(&|gs| gs.accusations[acc_idx].asked_players = Some(asked_players.clone())).is_err());
+    }
+}
@@ -0,0 +1,7 @@
+/// Somewhere a card can end up once the cards are dealt,
+/// either in a player's hand or in the envelope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+pub enum Holder {
+    Player(usize),
+    Envelope,
+}
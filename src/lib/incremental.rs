@@ -0,0 +1,356 @@
+use std::collections::VecDeque;
+
+use crate::accusation::{Accusation, FinalAccusation};
+use crate::deduction::Fact;
+use crate::game_state::GameState;
+use crate::holder::Holder;
+use crate::observation::Observation;
+use crate::solver::*;
+use crate::errors::*;
+
+/// Keeps a game state propagated as accusations and facts are added.
+///
+/// Rather than running every rule again like solver::propagate_state, each
+/// new fact goes on a worklist, and only the rules about the same card, hand
+/// or envelope category are checked again. Ends up with the same facts as
+/// propagate_state.
+pub struct IncrementalSolver {
+    gs: GameState,
+
+    /// Facts whose consequences haven't been applied yet
+    queue: VecDeque<Fact>,
+
+    /// Number of the game state's deductions already put on the queue
+    number_of_queued_deductions: usize,
+}
+
+impl IncrementalSolver {
+    /// Takes over the game state, applying everything already known about it.
+    pub fn new(gs: GameState) -> Result<IncrementalSolver> {
+        let number_of_queued_deductions = gs.deductions.len();
+
+        let mut solver = IncrementalSolver { gs, queue: VecDeque::new(), number_of_queued_deductions };
+
+        // Everything already known is new to the solver
+        for holder in solver.gs.get_holders() {
+            let hand = solver.gs.get_hand(holder);
+
+            let facts: Vec<Fact> = hand.must_have.iter()
+                .map(|card| Fact::new(holder, card, true))
+                .chain(hand.must_not_have.iter().map(|card| Fact::new(holder, card, false)))
+                .collect();
+
+            solver.queue.extend(facts);
+        }
+
+        apply_public_cards(&mut solver.gs);
+        apply_observations(&mut solver.gs);
+
+        for acc_idx in 0..solver.gs.accusations.len() {
+            apply_accusation_response(&mut solver.gs, acc_idx);
+            remember_unseen_card(&mut solver.gs, acc_idx);
+            apply_possible_responders(&mut solver.gs, acc_idx);
+        }
+
+        // Rules which might apply before anything is known
+        for player_idx in 0..solver.gs.player_hands.len() {
+            solver.apply_player_rules(player_idx);
+        }
+
+        for category_index in 0..solver.gs.deck.get_number_of_categories() {
+            apply_envelope_category(&mut solver.gs, category_index);
+        }
+
+        for final_idx in 0..solver.gs.final_accusations.len() {
+            apply_wrong_final_accusation(&mut solver.gs, final_idx);
+        }
+
+        apply_open_slot_counting(&mut solver.gs)?;
+
+        solver.run()?;
+
+        Ok(solver)
+    }
+
+    pub fn add_accusation(&mut self, accusation: Accusation) -> Result<()> {
+        let responses = self.gs.get_responses(&accusation);
+
+        self.gs.add_accusation(accusation);
+        let acc_idx = self.gs.accusations.len() - 1;
+
+        apply_accusation_response(&mut self.gs, acc_idx);
+
+        if remember_unseen_card(&mut self.gs, acc_idx) {
+            for response in responses {
+                apply_must_have_one_of(&mut self.gs, response.player_index);
+            }
+        }
+
+        self.apply_possible_responders(acc_idx);
+
+        self.run()
+    }
+
+    pub fn add_final_accusation(&mut self, final_accusation: FinalAccusation) -> Result<()> {
+        self.gs.add_final_accusation(final_accusation);
+        let final_idx = self.gs.final_accusations.len() - 1;
+
+        apply_wrong_final_accusation(&mut self.gs, final_idx);
+
+        self.run()
+    }
+
+    pub fn add_observation(&mut self, observation: Observation) -> Result<()> {
+        self.gs.add_observation(observation);
+
+        apply_observations(&mut self.gs);
+
+        self.run()
+    }
+
+    /// Adds a fact we were told rather than deduced, like a card seen by accident.
+    pub fn add_fact(&mut self, fact: Fact) -> Result<()> {
+        let hand = self.gs.get_hand_mut(fact.holder);
+
+        let is_new = if fact.has {
+            hand.must_have.insert(fact.card)
+        } else {
+            hand.must_not_have.insert(fact.card)
+        };
+
+        if is_new {
+            self.queue.push_back(fact);
+        }
+
+        self.run()
+    }
+
+    pub fn get_game_state(&self) -> &GameState {
+        &self.gs
+    }
+
+    pub fn into_game_state(self) -> GameState {
+        self.gs
+    }
+
+    /// Applies the consequences of every fact on the queue, until nothing new is learned.
+    fn run(&mut self) -> Result<()> {
+        loop {
+            self.queue_new_deductions();
+
+            let mut applied_facts: Vec<Fact> = Vec::new();
+
+            while let Some(fact) = self.queue.pop_front() {
+                self.apply_fact(&fact);
+                applied_facts.push(fact);
+                self.queue_new_deductions();
+            }
+
+            // The pigeonhole rule looks at groups of hands at once, so it's
+            // only checked once everything else has settled, and only for
+            // the groups the facts changed
+            if !apply_open_slot_counting_after(&mut self.gs, &applied_facts)? {
+                return Ok(());
+            }
+        }
+    }
+
+    fn queue_new_deductions(&mut self) {
+        let new_facts = self.gs.deductions[self.number_of_queued_deductions..].iter()
+            .map(|deduction| deduction.fact.clone());
+
+        self.queue.extend(new_facts);
+        self.number_of_queued_deductions = self.gs.deductions.len();
+    }
+
+    /// Checks the rules which depend on the fact again
+    fn apply_fact(&mut self, fact: &Fact) {
+        if fact.has {
+            apply_holder_has_card(&mut self.gs, fact.holder, &fact.card);
+        } else {
+            apply_only_place_left(&mut self.gs, &fact.card);
+        }
+
+        match fact.holder {
+            Holder::Player(player_idx) => {
+                self.apply_player_rules(player_idx);
+
+                if !fact.has {
+                    self.apply_possible_responders_including(player_idx);
+                }
+            },
+            Holder::Envelope => {
+                let category_index = self.gs.deck.get_category_index(&fact.card);
+                apply_envelope_category(&mut self.gs, category_index);
+
+                if fact.has {
+                    self.apply_final_accusations_naming(fact);
+                }
+            },
+        }
+    }
+
+    fn apply_final_accusations_naming(&mut self, fact: &Fact) {
+        for final_idx in 0..self.gs.final_accusations.len() {
+            if self.gs.final_accusations[final_idx].accused_cards.contains(&fact.card) {
+                apply_wrong_final_accusation(&mut self.gs, final_idx);
+            }
+        }
+    }
+
+    fn apply_possible_responders_including(&mut self, player_idx: usize) {
+        for acc_idx in 0..self.gs.accusations.len() {
+            if self.gs.accusations[acc_idx].possible_responders.contains(&player_idx) {
+                self.apply_possible_responders(acc_idx);
+            }
+        }
+    }
+
+    /// Narrows down who responded, checking the groups of cards of
+    /// whoever might now have to have one of them
+    fn apply_possible_responders(&mut self, acc_idx: usize) {
+        if apply_possible_responders(&mut self.gs, acc_idx) {
+            for player_idx in self.gs.accusations[acc_idx].possible_responders.clone() {
+                apply_must_have_one_of(&mut self.gs, player_idx);
+            }
+        }
+    }
+
+    fn apply_player_rules(&mut self, player_idx: usize) {
+        apply_hand_is_full(&mut self.gs, player_idx);
+        apply_fills_rest_of_hand(&mut self.gs, player_idx);
+        apply_must_have_one_of(&mut self.gs, player_idx);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::cards::*;
+    use crate::simulation::{miss_responders, simulate_game};
+    use crate::probability::get_card_probabilities;
+
+    use super::*;
+
+    /// Checks both know the same facts, and the same clauses in any order
+    fn assert_same_facts(gs: &GameState, other: &GameState) {
+        for holder in gs.get_holders() {
+            let hand = gs.get_hand(holder);
+            let other_hand = other.get_hand(holder);
+
+            assert_eq!(hand.must_have, other_hand.must_have, "{:?}", holder);
+            assert_eq!(hand.must_not_have, other_hand.must_not_have, "{:?}", holder);
+
+            assert_eq!(hand.must_have_one_of.len(), other_hand.must_have_one_of.len(), "{:?}", holder);
+            assert!(hand.must_have_one_of.iter().all(|clause| other_hand.must_have_one_of.contains(clause)), "{:?}", holder);
+        }
+    }
+
+    #[test]
+    fn test_incremental_solver_matches_propagate_state() {
+        for (number_of_players, seed) in [(3, 31), (4, 32), (5, 33), (6, 34), (6, 35)] {
+            let mut full_gs = simulate_game(number_of_players, seed, 20);
+            let accusations = std::mem::take(&mut full_gs.accusations);
+
+            let mut solver = IncrementalSolver::new(full_gs.clone()).unwrap();
+
+            for accusation in accusations {
+                full_gs.add_accusation(accusation.clone());
+                solver.add_accusation(accusation).unwrap();
+
+                let mut propagated_gs = full_gs.clone();
+                propagate_state(&mut propagated_gs).unwrap();
+
+                assert_same_facts(solver.get_game_state(), &propagated_gs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_solver_matches_benchmarked_games() {
+        // The games the benchmark times, clauses and all
+        for number_of_players in 3..=6 {
+            for seed in 0..5 {
+                let mut full_gs = simulate_game(number_of_players, seed, 30);
+                let accusations = std::mem::take(&mut full_gs.accusations);
+
+                let mut solver = IncrementalSolver::new(full_gs.clone()).unwrap();
+
+                for accusation in accusations {
+                    full_gs.add_accusation(accusation.clone());
+                    solver.add_accusation(accusation).unwrap();
+                }
+
+                propagate_state(&mut full_gs).unwrap();
+
+                assert_same_facts(solver.get_game_state(), &full_gs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_solver_possible_responders() {
+        for (number_of_players, seed) in [(4, 91), (5, 92), (6, 93)] {
+            let mut full_gs = simulate_game(number_of_players, seed, 20);
+            miss_responders(&mut full_gs);
+
+            let accusations = std::mem::take(&mut full_gs.accusations);
+
+            let mut solver = IncrementalSolver::new(full_gs.clone()).unwrap();
+
+            for accusation in accusations {
+                full_gs.add_accusation(accusation.clone());
+                solver.add_accusation(accusation).unwrap();
+
+                let mut propagated_gs = full_gs.clone();
+                propagate_state(&mut propagated_gs).unwrap();
+
+                assert_same_facts(solver.get_game_state(), &propagated_gs);
+            }
+        }
+    }
+
+    #[test]
+    fn test_incremental_solver_add_fact() {
+        let mut gs = simulate_game(4, 37, 6);
+
+        let mut solver = IncrementalSolver::new(gs.clone()).unwrap();
+
+        // Rule out a card which might be in the envelope
+        let probabilities = get_card_probabilities(&gs).unwrap();
+        let mut cards: Vec<Card> = gs.deck.get_all_cards().into_iter().collect();
+        cards.sort();
+
+        let card = cards.into_iter()
+            .find(|card| probabilities.get_probability(card, Holder::Envelope) > 0.0 
+                && probabilities.get_probability(card, Holder::Envelope) < 1.0)
+            .unwrap();
+
+        solver.add_fact(Fact::new(Holder::Envelope, card, false)).unwrap();
+        gs.envelope.must_not_have.insert(card);
+
+        propagate_state(&mut gs).unwrap();
+
+        assert_same_facts(solver.get_game_state(), &gs);
+    }
+
+    #[test]
+    fn test_incremental_solver_add_observation() {
+        let mut gs = simulate_game(5, 38, 8);
+        let hands = gs.reveal.clone().unwrap().player_hands.unwrap();
+
+        let mut solver = IncrementalSolver::new(gs.clone()).unwrap();
+
+        for card in hands[3].iter() {
+            let observation = Observation { player_index: 3, card, has: true };
+
+            solver.add_observation(observation.clone()).unwrap();
+            gs.add_observation(observation);
+
+            let mut propagated_gs = gs.clone();
+            propagate_state(&mut propagated_gs).unwrap();
+
+            assert_same_facts(solver.get_game_state(), &propagated_gs);
+        }
+    }
+}
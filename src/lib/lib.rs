@@ -3,12 +3,30 @@ pub mod player_hand;
 pub mod accusation;
 pub mod game_state;
 pub mod cards;
+pub mod card_set;
+pub mod deck;
+pub mod holder;
+pub mod probability;
+pub mod constraint_solver;
+pub mod deduction;
+pub mod mistakes;
+pub mod incremental;
+pub mod audit;
+pub mod rules;
+pub mod observation;
+pub mod merge;
+pub mod advice;
+pub mod threats;
+
+#[cfg(any(test, feature = "simulation"))]
+pub mod simulation;
 
 
 pub mod errors {
-    use error_chain::error_chain;
+    // error_chain generates cfgs that newer compilers don't know about
+    #![allow(unexpected_cfgs)]
 
-    use crate::cards::Card;
+    use error_chain::error_chain;
 
     error_chain!{
         errors {
@@ -16,11 +34,15 @@ pub mod errors {
                 description("Player has an invalid number of cards")
                 display("\"{}\" has {} {}, while the limit is {}", player, actual, list_type, limit)
             }
-            PlayerCardContradiction(player: String, card: Card, reason: String) {
+            PlayerCardContradiction(player: String, card: String, reason: String) {
                 description("Player is contradictory to other info")
                 display("\"{}\" has contradictory card \"{}\" because {}", player, card, reason)
             }
-            PlayerHasPublicCard(player: String, cards: Card) {
+            PlayerMustHaveOneOfContradiction(player: String, cards: String) {
+                description("Player must have one of a group of cards they must not have")
+                display("\"{}\" must have one of {}, yet must not have any of them", player, cards)
+            }
+            PlayerHasPublicCard(player: String, cards: String) {
                 description("Player must have a publicly shown card")
                 display("\"{}\" has public card \"{}\"", player, cards)
             }
@@ -37,6 +59,66 @@ pub mod errors {
                 description("accusation card is shown, yet responding player is none")
                 display("{}'s Accusation says card \"{}\" is shown, yet responding player is none", accusing_player, shown_card)
             }
+            InvalidResponses(accusing_player: String, reason: String) {
+                description("accusation responses don't fit the house rules")
+                display("{}'s accusation has responses that don't fit the house rules, {}", accusing_player, reason)
+            }
+            InvalidAskingOrder(accusing_player: String, reason: String) {
+                description("accusation asks players in an order that can't happen")
+                display("{}'s accusation asks players in an order that can't happen, {}", accusing_player, reason)
+            }
+            DummyHandTakesTurn(player: String) {
+                description("A dummy hand made an accusation")
+                display("\"{}\" is a dummy hand, yet made an accusation", player)
+            }
+            FinalAccusationContradiction(accusing_player: String) {
+                description("final accusation was wrong, yet the envelope has every card named")
+                display("{}'s final accusation was wrong, yet the envelope must have every card they named", accusing_player)
+            }
+            NoConsistentDeals {
+                description("No deal of the cards is consistent with the game state")
+                display("no deal of the cards is consistent with the game state")
+            }
+            UnsupportedGameSize(reason: String) {
+                description("Game is too large to count deals for")
+                display("game is too large to count deals for, {}", reason)
+            }
+            TooManyOpenHolders(actual: usize, limit: usize) {
+                description("Too many hands and envelope categories could still take a card")
+                display("{} hands and envelope categories could still take a card, while the limit is {}", actual, limit)
+            }
+            SpectatorHasNoHand {
+                description("Watching the game, so there is no hand of our own")
+                display("you're watching the game, so have no hand of your own")
+            }
+            CardsDontMatchDeck(deck: String, reason: String) {
+                description("Cards in the game aren't from its deck")
+                display("the cards saved don't match the {} deck, {}", deck, reason)
+            }
+            InvalidReveal(reason: String) {
+                description("Cards revealed couldn't have been dealt")
+                display("the cards revealed couldn't have been dealt, {}", reason)
+            }
+            CannotMerge(reason: String) {
+                description("Game states are from different games")
+                display("can't merge the game states, {}", reason)
+            }
+            TooFewPlayers(actual: usize, minimum: usize) {
+                description("Game has too few players to be played")
+                display("there are {} players, while a game needs at least {}", actual, minimum)
+            }
+            InvalidHandSizes(reason: String) {
+                description("Hand sizes don't add up to the cards dealt")
+                display("hand sizes don't add up, {}", reason)
+            }
+            UnknownEdition(edition: String, editions: String) {
+                description("No edition has that name")
+                display("there is no {} edition, the editions are {}", edition, editions)
+            }
+            InvalidDeck(deck: String, reason: String) {
+                description("Deck can't be played with")
+                display("the {} deck can't be played with, because {}", deck, reason)
+            }
         }
 
         foreign_links {
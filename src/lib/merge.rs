@@ -0,0 +1,420 @@
+use error_chain::bail;
+
+use crate::accusation::{Accusation, Response};
+use crate::deduction::Fact;
+use crate::game_state::GameState;
+use crate::holder::Holder;
+use crate::observation::Observation;
+use crate::mistakes::get_given_facts;
+use crate::solver::propagate_state;
+use crate::errors::*;
+
+/// Where two game states being merged disagree. The first game state's
+/// version is kept.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Conflict {
+    /// Our and their index of a turn where different accusations were logged
+    Accusation(usize, usize),
+
+    /// Our and their index of a turn where different players were logged as
+    /// asked or responding, or different cards as shown
+    Response(usize, usize),
+
+    /// A fact about one teammate's hand, while the other was given or saw the opposite
+    GivenFact(Fact),
+
+    /// Index of a player logged with a different number of cards
+    HandSize(usize),
+}
+
+/// Combines two teammates' views of the same game into one state, which
+/// knows everything either of them does, then propagates it.
+///
+/// Accusations are lined up by who made them and which cards they named,
+/// so a turn only one teammate logged is kept, taking the card shown from
+/// whichever teammate saw it. The first game state's seat is kept as self,
+/// while the second's hand is carried over as observations.
+///
+/// The games must have the same deck, players, public cards and house rules.
+pub fn merge_game_states(ours: &GameState, theirs: &GameState) -> Result<(GameState, Vec<Conflict>)> {
+    if ours.deck != theirs.deck {
+        bail!(ErrorKind::CannotMerge("the games use different decks".to_owned()));
+    }
+
+    let our_names: Vec<&String> = ours.player_hands.iter().map(|hand| &hand.player_name).collect();
+    let their_names: Vec<&String> = theirs.player_hands.iter().map(|hand| &hand.player_name).collect();
+
+    if our_names != their_names {
+        bail!(ErrorKind::CannotMerge("the games have different players, or seat them differently".to_owned()));
+    }
+
+    if ours.public_cards != theirs.public_cards {
+        bail!(ErrorKind::CannotMerge("the games have different face up cards".to_owned()));
+    }
+
+    if ours.rules != theirs.rules {
+        bail!(ErrorKind::CannotMerge("the games are played with different house rules".to_owned()));
+    }
+
+    let mut conflicts: Vec<Conflict> = Vec::new();
+    let mut merged = ours.clone();
+
+    for (player_idx, (our_hand, their_hand)) in ours.player_hands.iter().zip(theirs.player_hands.iter()).enumerate() {
+        match (our_hand.hand_size, their_hand.hand_size) {
+            (Some(our_size), Some(their_size)) if our_size != their_size => conflicts.push(Conflict::HandSize(player_idx)),
+            (None, Some(their_size)) => merged.player_hands[player_idx].hand_size = Some(their_size),
+            _ => {},
+        }
+    }
+
+    let given_facts = get_given_facts(ours);
+
+    // Their hand is carried over as seen, since only our own hand is given
+    for fact in get_given_facts(theirs) {
+        let opposite = Fact::new(fact.holder, fact.card, !fact.has);
+
+        if given_facts.contains(&opposite) || ours.observations.iter().any(|observation| observation.get_fact() == opposite) {
+            conflicts.push(Conflict::GivenFact(fact));
+            continue;
+        }
+
+        if let Holder::Player(player_index) = fact.holder {
+            let observation = Observation { player_index, card: fact.card, has: fact.has };
+
+            if !merged.observations.contains(&observation) {
+                merged.observations.push(observation);
+            }
+        }
+    }
+
+    // Start again from only what was given, so nothing is deduced from turns that conflict
+    for holder in merged.get_holders() {
+        let hand = merged.get_hand_mut(holder);
+        hand.must_have.clear();
+        hand.must_not_have.clear();
+        hand.must_have_one_of.clear();
+    }
+
+    merged.deductions.clear();
+
+    for fact in given_facts {
+        let hand = merged.get_hand_mut(fact.holder);
+
+        if fact.has {
+            hand.must_have.insert(fact.card);
+        } else {
+            hand.must_not_have.insert(fact.card);
+        }
+    }
+
+    merged.accusations.clear();
+
+    let mut our_turn = 0;
+    let mut their_turn = 0;
+
+    // Between turns that line up, any logged by both of us differ, while any
+    // logged by only one of us were missed by the other
+    let lined_up_turns = get_lined_up_turns(&ours.accusations, &theirs.accusations);
+
+    for (our_next, their_next) in lined_up_turns.into_iter().chain([(ours.accusations.len(), theirs.accusations.len())]) {
+        for (our_missed, their_missed) in (our_turn..our_next).zip(their_turn..their_next) {
+            conflicts.push(Conflict::Accusation(our_missed, their_missed));
+        }
+
+        merged.accusations.extend_from_slice(&ours.accusations[our_turn..our_next]);
+
+        let their_extra_turns = their_turn + (our_next - our_turn).min(their_next - their_turn);
+        merged.accusations.extend_from_slice(&theirs.accusations[their_extra_turns..their_next]);
+
+        if our_next == ours.accusations.len() {
+            break;
+        }
+
+        let our_acc = &ours.accusations[our_next];
+
+        match merge_accusations(our_acc, &theirs.accusations[their_next]) {
+            Some(acc) => merged.accusations.push(acc),
+            None => {
+                conflicts.push(Conflict::Response(our_next, their_next));
+                merged.accusations.push(our_acc.clone());
+            },
+        }
+
+        our_turn = our_next + 1;
+        their_turn = their_next + 1;
+    }
+
+    for final_accusation in theirs.final_accusations.iter() {
+        if !merged.final_accusations.contains(final_accusation) {
+            merged.final_accusations.push(final_accusation.clone());
+        }
+    }
+
+    for observation in theirs.observations.iter() {
+        if !merged.observations.contains(observation) {
+            merged.observations.push(observation.clone());
+        }
+    }
+
+    if merged.reveal.is_none() {
+        merged.reveal = theirs.reveal.clone();
+    }
+
+    propagate_state(&mut merged)?;
+
+    Ok((merged, conflicts))
+}
+
+/// Pairs of our and their turns with the same accuser and accused cards,
+/// in order, lining up as many turns as possible.
+fn get_lined_up_turns(ours: &[Accusation], theirs: &[Accusation]) -> Vec<(usize, usize)> {
+    let is_same_turn = |our_acc: &Accusation, their_acc: &Accusation|
+        our_acc.accuser_player_index == their_acc.accuser_player_index && our_acc.accused_cards == their_acc.accused_cards;
+
+    // Most turns that line up from each pair of our and their turns onwards
+    let mut most_lined_up = vec![vec![0; theirs.len() + 1]; ours.len() + 1];
+
+    for our_turn in (0..ours.len()).rev() {
+        for their_turn in (0..theirs.len()).rev() {
+            most_lined_up[our_turn][their_turn] = if is_same_turn(&ours[our_turn], &theirs[their_turn]) {
+                most_lined_up[our_turn + 1][their_turn + 1] + 1
+            } else {
+                most_lined_up[our_turn + 1][their_turn].max(most_lined_up[our_turn][their_turn + 1])
+            };
+        }
+    }
+
+    let mut lined_up_turns: Vec<(usize, usize)> = Vec::new();
+    let (mut our_turn, mut their_turn) = (0, 0);
+
+    while our_turn < ours.len() && their_turn < theirs.len() {
+        if is_same_turn(&ours[our_turn], &theirs[their_turn]) {
+            lined_up_turns.push((our_turn, their_turn));
+            our_turn += 1;
+            their_turn += 1;
+        } else if most_lined_up[our_turn + 1][their_turn] >= most_lined_up[our_turn][their_turn + 1] {
+            our_turn += 1;
+        } else {
+            their_turn += 1;
+        }
+    }
+
+    lined_up_turns
+}
+
+/// The accusation with what both teammates know about it, or None if they
+/// logged different responses
+fn merge_accusations(ours: &Accusation, theirs: &Accusation) -> Option<Accusation> {
+    let mut merged = ours.clone();
+
+    merged.asked_players = match (&ours.asked_players, &theirs.asked_players) {
+        (Some(our_asked), Some(their_asked)) if our_asked != their_asked => return None,
+        (our_asked, their_asked) => our_asked.clone().or(their_asked.clone()),
+    };
+
+    merged.card_shown = match (ours.card_shown, theirs.card_shown) {
+        (Some(our_card), Some(their_card)) if our_card != their_card => return None,
+        (our_card, their_card) => our_card.or(their_card),
+    };
+
+    // Who responded, narrowed down to who both think it could have been
+    let our_responders = get_possible_responders(ours);
+    let their_responders = get_possible_responders(theirs);
+
+    let responders: Vec<usize> = our_responders.iter()
+        .filter(|player_idx| their_responders.contains(player_idx))
+        .copied()
+        .collect();
+
+    if responders.is_empty() != our_responders.is_empty() || responders.is_empty() != their_responders.is_empty() {
+        return None;
+    }
+
+    if responders.len() == 1 {
+        merged.responding_player_index = Some(responders[0]);
+        merged.possible_responders = Vec::new();
+    } else {
+        merged.responding_player_index = None;
+        merged.possible_responders = responders;
+    }
+
+    if merged.card_shown.is_some() && merged.responding_player_index.is_none() {
+        return None;
+    }
+
+    // Everyone who can shows a card, so the same players responded
+    let mut our_response_players: Vec<usize> = ours.responses.iter().map(|response| response.player_index).collect();
+    let mut their_response_players: Vec<usize> = theirs.responses.iter().map(|response| response.player_index).collect();
+    our_response_players.sort();
+    their_response_players.sort();
+
+    if our_response_players != their_response_players {
+        return None;
+    }
+
+    for response in merged.responses.iter_mut() {
+        let their_response: &Response = theirs.responses.iter()
+            .find(|their_response| their_response.player_index == response.player_index)
+            .unwrap();
+
+        response.card_shown = match (response.card_shown, their_response.card_shown) {
+            (Some(our_card), Some(their_card)) if our_card != their_card => return None,
+            (our_card, their_card) => our_card.or(their_card),
+        };
+    }
+
+    Some(merged)
+}
+
+/// Players who could have responded, empty if nobody did
+fn get_possible_responders(acc: &Accusation) -> Vec<usize> {
+    match acc.responding_player_index {
+        Some(responder) => vec![responder],
+        None => acc.possible_responders.clone(),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::find_wrong_facts;
+    use crate::card_set::CardSet;
+    use crate::simulation::simulate_game;
+    use crate::deck::{Deck, tests::card};
+    use crate::player_hand::PlayerHand;
+
+    use super::*;
+
+    /// The same game as seen by another player
+    fn get_view_of(gs: &GameState, player_idx: usize) -> GameState {
+        let hands = gs.reveal.clone().unwrap().player_hands.unwrap();
+
+        let mut view = gs.clone();
+        view.self_index = Some(player_idx);
+
+        for (hand_idx, hand) in view.player_hands.iter_mut().enumerate() {
+            *hand = PlayerHand::new(hand.player_name.clone());
+
+            if hand_idx == player_idx {
+                hand.must_have = hands[hand_idx];
+            }
+        }
+
+        for acc in view.accusations.iter_mut() {
+            acc.card_shown = acc.responding_player_index
+                .filter(|responder| *responder == player_idx || acc.accuser_player_index == player_idx)
+                .and_then(|responder| acc.accused_cards.iter().find(|card| hands[responder].contains(card)).copied());
+        }
+
+        view
+    }
+
+    #[test]
+    fn test_merge_game_states_combines_private_knowledge() {
+        for (number_of_players, seed) in [(3, 111), (4, 112), (5, 113)] {
+            let gs = simulate_game(number_of_players, seed, 12);
+
+            let mut ours = get_view_of(&gs, 0);
+            let mut theirs = get_view_of(&gs, 1);
+
+            propagate_state(&mut ours).unwrap();
+            propagate_state(&mut theirs).unwrap();
+
+            let (merged, conflicts) = merge_game_states(&ours, &theirs).unwrap();
+
+            assert_eq!(conflicts, Vec::new());
+            assert_eq!(merged.self_index, Some(0));
+            merged.verify_state().unwrap();
+
+            // Knows everything either teammate does, and nothing wrong
+            for view in [&ours, &theirs] {
+                for holder in view.get_holders() {
+                    assert!(view.get_hand(holder).must_have.is_subset(&merged.get_hand(holder).must_have));
+                    assert!(view.get_hand(holder).must_not_have.is_subset(&merged.get_hand(holder).must_not_have));
+                }
+            }
+
+            assert!(find_wrong_facts(&merged, &gs.reveal.clone().unwrap()).is_empty());
+
+            for (turn, acc) in merged.accusations.iter().enumerate() {
+                assert_eq!(acc.card_shown, ours.accusations[turn].card_shown.or(theirs.accusations[turn].card_shown));
+            }
+        }
+    }
+
+    #[test]
+    fn test_merge_game_states_reports_conflicts() {
+        let gs = simulate_game(4, 114, 8);
+
+        let ours = get_view_of(&gs, 0);
+        let mut theirs = get_view_of(&gs, 1);
+
+        // Logged a different responder
+        let turn = theirs.accusations.iter()
+            .position(|acc| acc.responding_player_index.is_some() && acc.card_shown.is_none())
+            .unwrap();
+
+        let acc = &mut theirs.accusations[turn];
+        acc.responding_player_index = (0..4)
+            .find(|player_idx| *player_idx != acc.accuser_player_index && Some(*player_idx) != acc.responding_player_index);
+
+        // And one more turn than us
+        theirs.accusations.push(gs.accusations[0].clone());
+
+        let (merged, conflicts) = merge_game_states(&ours, &theirs).unwrap();
+
+        assert_eq!(conflicts, vec![Conflict::Response(turn, turn)]);
+        assert_eq!(merged.accusations[turn], ours.accusations[turn]);
+        assert_eq!(merged.accusations.len(), ours.accusations.len() + 1);
+
+        // Not the same game
+        let mut other_game = theirs.clone();
+        other_game.deck = Deck::uk();
+        assert!(merge_game_states(&ours, &other_game).is_err());
+
+        let mut other_game = theirs.clone();
+        other_game.public_cards = CardSet::new();
+        other_game.public_cards.insert(gs.deck.get_all_cards().iter().next().unwrap());
+        assert!(merge_game_states(&ours, &other_game).is_err());
+
+        // Logged a different accusation
+        let mut theirs = get_view_of(&gs, 1);
+        let room = theirs.accusations[3].accused_cards[0];
+        theirs.accusations[3].accused_cards[0] = if room == card("Kitchen") { card("Hall") } else { card("Kitchen") };
+
+        let (merged, conflicts) = merge_game_states(&ours, &theirs).unwrap();
+
+        assert_eq!(conflicts, vec![Conflict::Accusation(3, 3)]);
+        assert_eq!(merged.accusations.len(), ours.accusations.len());
+        assert_eq!(merged.accusations[3], ours.accusations[3]);
+    }
+
+    #[test]
+    fn test_merge_game_states_lines_up_missed_turns() {
+        let gs = simulate_game(4, 115, 12);
+
+        let mut ours = get_view_of(&gs, 0);
+        let mut theirs = get_view_of(&gs, 1);
+
+        // We each stepped away for a different turn
+        ours.accusations.remove(8);
+        theirs.accusations.remove(3);
+
+        propagate_state(&mut ours).unwrap();
+        propagate_state(&mut theirs).unwrap();
+
+        let (merged, conflicts) = merge_game_states(&ours, &theirs).unwrap();
+
+        assert_eq!(conflicts, Vec::new());
+        merged.verify_state().unwrap();
+        assert!(find_wrong_facts(&merged, &gs.reveal.clone().unwrap()).is_empty());
+
+        assert_eq!(merged.accusations.len(), gs.accusations.len());
+
+        for (merged_acc, acc) in merged.accusations.iter().zip(gs.accusations.iter()) {
+            assert_eq!(merged_acc.accuser_player_index, acc.accuser_player_index);
+            assert_eq!(merged_acc.accused_cards, acc.accused_cards);
+            assert_eq!(merged_acc.responding_player_index, acc.responding_player_index);
+        }
+    }
+}
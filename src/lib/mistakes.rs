@@ -0,0 +1,419 @@
+use crate::game_state::GameState;
+use crate::deduction::Fact;
+use crate::holder::Holder;
+use crate::constraint_solver::{is_consistent, propagate_state_tolerant};
+use crate::probability::count_consistent_deals;
+use crate::errors::*;
+
+/// Something entered into the game, which may have been entered wrong.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Entry {
+    /// Index of an accusation
+    Accusation(usize),
+
+    /// A fact that was given rather than deduced, like the cards in your hand
+    GivenFact(Fact),
+
+    /// Index of an observation
+    Observation(usize),
+
+    /// Index of a wrong final accusation
+    FinalAccusation(usize),
+}
+
+/// Every entry in the game, given facts first, then the observations, the
+/// accusations and the final accusations in order.
+///
+/// Public cards aren't included, since no deal works without them.
+pub fn get_entries(gs: &GameState) -> Vec<Entry> {
+    get_given_facts(gs).into_iter()
+        .map(Entry::GivenFact)
+        .chain((0..gs.observations.len()).map(Entry::Observation))
+        .chain((0..gs.accusations.len()).map(Entry::Accusation))
+        .chain((0..gs.final_accusations.len()).map(Entry::FinalAccusation))
+        .collect()
+}
+
+/// Facts about your hand that were given rather than deduced, in card order.
+///
+/// Only your own hand is given, anything else known about a hand comes from
+/// the observations and accusations, even in older saves without deductions.
+/// Public cards aren't included, since they're never anywhere.
+pub fn get_given_facts(gs: &GameState) -> Vec<Fact> {
+    let self_index = match gs.self_index {
+        Some(self_index) => self_index,
+        None => return Vec::new(),
+    };
+
+    let holder = Holder::Player(self_index);
+    let hand = &gs.player_hands[self_index];
+
+    let mut given_facts: Vec<Fact> = hand.must_have.iter()
+        .map(|card| Fact::new(holder, card, true))
+        .chain(hand.must_not_have.iter()
+            .filter(|card| !gs.public_cards.contains(card))
+            .map(|card| Fact::new(holder, card, false)))
+        .collect();
+
+    given_facts.retain(|fact| gs.get_deduction(fact).is_none());
+    given_facts.sort_by_key(|fact| (fact.card, fact.has));
+
+    given_facts
+}
+
+/// The game as if only the given entries had been entered, with nothing deduced yet.
+pub fn get_game_state_with_entries(gs: &GameState, entries: &[Entry]) -> GameState {
+    let mut entries_gs = gs.clone();
+
+    for holder in entries_gs.get_holders() {
+        let hand = entries_gs.get_hand_mut(holder);
+        hand.must_have.clear();
+        hand.must_not_have.clear();
+        hand.must_have_one_of.clear();
+    }
+
+    entries_gs.accusations.clear();
+    entries_gs.final_accusations.clear();
+    entries_gs.observations.clear();
+    entries_gs.deductions.clear();
+
+    for entry in entries {
+        match entry {
+            Entry::Accusation(acc_idx) => entries_gs.accusations.push(gs.accusations[*acc_idx].clone()),
+            Entry::Observation(obs_idx) => entries_gs.observations.push(gs.observations[*obs_idx].clone()),
+            Entry::FinalAccusation(final_idx) => entries_gs.final_accusations.push(gs.final_accusations[*final_idx].clone()),
+            Entry::GivenFact(fact) => {
+                let hand = entries_gs.get_hand_mut(fact.holder);
+
+                if fact.has {
+                    hand.must_have.insert(fact.card);
+                } else {
+                    hand.must_not_have.insert(fact.card);
+                }
+            }
+        }
+    }
+
+    entries_gs
+}
+
+/// Finds a minimal set of entries which can't all be true together, so at
+/// least one of them is a mistake. None if there's no contradiction.
+///
+/// Every entry returned is needed for the contradiction, leaving out any one
+/// of them makes the rest consistent. It isn't necessarily the smallest such
+/// set, as a different set of entries may contradict each other with fewer.
+/// Entries are returned in the same order as get_entries.
+pub fn find_contradiction_core(gs: &GameState) -> Option<Vec<Entry>> {
+    let entries = get_entries(gs);
+
+    if is_consistent(&get_game_state_with_entries(gs, &entries)) {
+        return None;
+    }
+
+    // Entries are left out one at a time, keeping those the contradiction needs.
+    // Facts that something isn't in a hand say little on their own, while a card
+    // in a hand rules it out everywhere else, so trying them in this order tends
+    // to keep the set small.
+    let mut removal_order: Vec<usize> = (0..entries.len()).collect();
+    removal_order.sort_by_key(|entry_idx| match &entries[*entry_idx] {
+        Entry::GivenFact(fact) if !fact.has => (0, 0),
+        Entry::Observation(obs_idx) if !gs.observations[*obs_idx].has => (0, 0),
+        Entry::Accusation(acc_idx) => (1, usize::MAX - acc_idx),
+        Entry::FinalAccusation(_) => (1, 0),
+        Entry::GivenFact(_) | Entry::Observation(_) => (2, 0),
+    });
+
+    let mut in_core = vec![true; entries.len()];
+
+    for entry_idx in removal_order {
+        in_core[entry_idx] = false;
+
+        let remaining: Vec<Entry> = entries.iter()
+            .zip(in_core.iter())
+            .filter(|(_, in_core)| **in_core)
+            .map(|(entry, _)| entry.clone())
+            .collect();
+
+        if is_consistent(&get_game_state_with_entries(gs, &remaining)) {
+            // Needed for the contradiction
+            in_core[entry_idx] = true;
+        }
+    }
+
+    Some(entries.into_iter()
+        .zip(in_core)
+        .filter(|(_, in_core)| *in_core)
+        .map(|(entry, _)| entry)
+        .collect())
+}
+
+/// The game allowing for up to tolerance accusations to be wrong.
+///
+/// Holds the given facts, plus every fact that is true no matter which
+/// accusations are ignored.
+pub fn get_tolerant_game_state(gs: &GameState, tolerance: usize) -> Result<GameState> {
+    let mut tolerant_gs = get_game_state_with_entries(gs, &get_entries(gs));
+
+    propagate_state_tolerant(&mut tolerant_gs, tolerance)?;
+
+    Ok(tolerant_gs)
+}
+
+/// Ranks the sets of up to k entries which could be the mistakes, most
+/// likely first.
+///
+/// A set could be the mistakes if the rest of the entries are consistent
+/// without it, and no smaller set inside it would do. Each comes with the
+/// number of deals consistent with the rest. Fewer mistakes are more likely
+/// than more, and among sets of the same size, the more deals a set rules
+/// out the less likely it is to be right.
+///
+/// If nothing contradicts, every entry could be a mistake on its own, so only
+/// single entries are ranked.
+pub fn rank_likely_mistakes(gs: &GameState, k: usize) -> Result<Vec<(Vec<Entry>, u128)>> {
+    let entries = get_entries(gs);
+
+    let mut ranked: Vec<(Vec<usize>, u128)> = Vec::new();
+
+    for size in 1..=k.min(entries.len()) {
+        let mut found: Vec<(Vec<usize>, u128)> = Vec::new();
+
+        for left_out in get_index_subsets(entries.len(), size) {
+            // Holds a smaller set which would already do
+            if ranked.iter().any(|(smaller, _)| smaller.iter().all(|entry_idx| left_out.contains(entry_idx))) {
+                continue;
+            }
+
+            let other_entries: Vec<Entry> = entries.iter()
+                .enumerate()
+                .filter(|(entry_idx, _)| !left_out.contains(entry_idx))
+                .map(|(_, entry)| entry.clone())
+                .collect();
+
+            let deals = count_consistent_deals(&get_game_state_with_entries(gs, &other_entries))?;
+
+            if deals > 0 {
+                found.push((left_out, deals));
+            }
+        }
+
+        // Stable, so ties stay in entry order
+        found.sort_by(|(_, deals), (_, other_deals)| other_deals.cmp(deals));
+        ranked.extend(found);
+    }
+
+    Ok(ranked.into_iter()
+        .map(|(left_out, deals)| (left_out.into_iter().map(|entry_idx| entries[entry_idx].clone()).collect(), deals))
+        .collect())
+}
+
+/// Every set of size indexes below number_of_indexes, each in increasing order.
+fn get_index_subsets(number_of_indexes: usize, size: usize) -> Vec<Vec<usize>> {
+    if size == 0 {
+        return vec![Vec::new()];
+    }
+
+    (size - 1..number_of_indexes)
+        .flat_map(|last| get_index_subsets(last, size - 1).into_iter()
+            .map(move |mut subset| {
+                subset.push(last);
+                subset
+            }))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::card_set::CardSet;
+    use crate::accusation::{Accusation, FinalAccusation};
+    use crate::cards::*;
+    use crate::deck::{Deck, tests::card};
+    use crate::holder::Holder;
+    use crate::observation::Observation;
+    use crate::player_hand::PlayerHand;
+    use crate::solver::propagate_state;
+
+    use super::*;
+
+    fn new_game_state(accusations: Vec<Accusation>) -> GameState {
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
+
+        player_hands[0].must_have.extend([
+            card("Kitchen"), card("Hall"), card("Study"),
+            card("Rope"), card("Plum"), card("White"),
+        ]);
+
+        GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations,
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        }
+    }
+
+    fn new_accusation(accuser: usize, room: Card, weapon: Card, suspect: Card,
+        responder: Option<usize>, card_shown: Option<Card>) -> Accusation {
+
+        Accusation {
+            accuser_player_index: accuser,
+            accused_cards: vec![room, weapon, suspect],
+            responding_player_index: responder,
+            card_shown,
+            responses: Vec::new(),
+            asked_players: None,
+            possible_responders: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_find_contradiction_core_none_when_consistent() {
+        let mut gs = new_game_state(vec![
+            new_accusation(1, card("Lounge"), card("Knife"), card("Green"), Some(2), None),
+        ]);
+
+        propagate_state(&mut gs).unwrap();
+
+        assert_eq!(find_contradiction_core(&gs), None);
+    }
+
+    #[test]
+    fn test_find_contradiction_core_shown_card_in_own_hand() {
+        let mut gs = new_game_state(vec![
+            new_accusation(1, card("Lounge"), card("Knife"), card("Green"), Some(2), None),
+            // p2 can't have shown the Kitchen, p1 has it
+            new_accusation(0, card("Kitchen"), card("Knife"), card("Green"), Some(1), Some(card("Kitchen"))),
+            new_accusation(2, card("Lounge"), card("Pipe"), card("Scarlet"), None, None),
+        ]);
+
+        propagate_state(&mut gs).unwrap();
+
+        let core = find_contradiction_core(&gs).unwrap();
+
+        assert_eq!(core.len(), 2);
+        assert!(core.contains(&Entry::Accusation(1)));
+        assert!(core.contains(&Entry::GivenFact(Fact::new(Holder::Player(0), card("Kitchen"), true))));
+    }
+
+    #[test]
+    fn test_find_contradiction_core_between_accusations() {
+        let gs = new_game_state(vec![
+            new_accusation(0, card("Lounge"), card("Knife"), card("Green"), Some(1), Some(card("Knife"))),
+            new_accusation(2, card("Kitchen"), card("Pipe"), card("Scarlet"), Some(0), None),
+            // p2 was asked before p3, yet couldn't show the Knife they showed earlier
+            new_accusation(0, card("Lounge"), card("Knife"), card("Mustard"), Some(2), None),
+        ]);
+
+        let core = find_contradiction_core(&gs).unwrap();
+
+        assert_eq!(core, vec![Entry::Accusation(0), Entry::Accusation(2)]);
+    }
+
+    #[test]
+    fn test_get_tolerant_game_state_ignores_mistake() {
+        let shown_lounge = new_accusation(0, card("Lounge"), card("Knife"), card("Green"), Some(2), Some(card("Lounge")));
+        let p3_has_lounge = Fact::new(Holder::Player(2), card("Lounge"), true);
+
+        // With one mistake allowed, the Lounge could be wrong
+        let gs = new_game_state(vec![shown_lounge.clone()]);
+        let tolerant_gs = get_tolerant_game_state(&gs, 1).unwrap();
+        assert!(!tolerant_gs.is_known(&p3_has_lounge));
+
+        // Unless the mistake has to be elsewhere
+        let gs = new_game_state(vec![
+            shown_lounge,
+            new_accusation(0, card("Kitchen"), card("Knife"), card("Green"), Some(1), Some(card("Kitchen"))),
+        ]);
+        let tolerant_gs = get_tolerant_game_state(&gs, 1).unwrap();
+        assert!(tolerant_gs.is_known(&p3_has_lounge));
+
+        // Your own hand is never in doubt
+        assert!(tolerant_gs.is_known(&Fact::new(Holder::Player(1), card("Kitchen"), false)));
+    }
+
+    #[test]
+    fn test_get_tolerant_game_state_from_save_without_deductions() {
+        let shown_lounge = new_accusation(0, card("Lounge"), card("Knife"), card("Green"), Some(2), Some(card("Lounge")));
+        let p3_has_lounge = Fact::new(Holder::Player(2), card("Lounge"), true);
+
+        // Saved before deductions were recorded, so nothing says how p3 got the Lounge
+        let mut gs = new_game_state(vec![shown_lounge]);
+        propagate_state(&mut gs).unwrap();
+        gs.deductions.clear();
+
+        assert!(get_given_facts(&gs).iter().all(|fact| fact.holder == Holder::Player(0)));
+        assert!(!get_given_facts(&gs).contains(&p3_has_lounge));
+
+        let tolerant_gs = get_tolerant_game_state(&gs, 1).unwrap();
+        assert!(!tolerant_gs.is_known(&p3_has_lounge));
+    }
+
+    #[test]
+    fn test_rank_likely_mistakes() {
+        let gs = new_game_state(vec![
+            new_accusation(1, card("Lounge"), card("Knife"), card("Green"), Some(2), None),
+            new_accusation(0, card("Kitchen"), card("Knife"), card("Green"), Some(1), Some(card("Kitchen"))),
+            new_accusation(2, card("Lounge"), card("Pipe"), card("Scarlet"), None, None),
+        ]);
+
+        let p1_has_kitchen = Entry::GivenFact(Fact::new(Holder::Player(0), card("Kitchen"), true));
+
+        // Either p2 didn't show the Kitchen, or we don't have it
+        let ranked = rank_likely_mistakes(&gs, 2).unwrap();
+
+        assert_eq!(ranked.len(), 2);
+        assert!(ranked.iter().all(|(entries, deals)| entries.len() == 1 && *deals > 0));
+        assert!(ranked.iter().any(|(entries, _)| entries == &vec![Entry::Accusation(1)]));
+        assert!(ranked.iter().any(|(entries, _)| entries == &vec![p1_has_kitchen.clone()]));
+        assert!(ranked[0].1 >= ranked[1].1);
+    }
+
+    #[test]
+    fn test_rank_likely_mistakes_needing_two() {
+        let mut gs = new_game_state(vec![
+            new_accusation(0, card("Ballroom"), card("Pipe"), card("Mustard"), Some(1), Some(card("Ballroom"))),
+            // Nobody else has these, and neither do we
+            new_accusation(0, card("Lounge"), card("Knife"), card("Green"), None, None),
+        ]);
+
+        // Yet p2 was seen without the Ballroom
+        gs.observations.push(Observation { player_index: 1, card: card("Ballroom"), has: false });
+
+        // And p2 was wrong to accuse what must be in the envelope
+        gs.final_accusations.push(FinalAccusation {
+            accuser_player_index: 1,
+            accused_cards: vec![card("Lounge"), card("Knife"), card("Green")],
+        });
+
+        // No single entry is the mistake
+        assert!(rank_likely_mistakes(&gs, 1).unwrap().is_empty());
+
+        let ranked = rank_likely_mistakes(&gs, 2).unwrap();
+
+        assert!(!ranked.is_empty());
+        assert!(ranked.iter().all(|(entries, deals)| entries.len() == 2 && *deals > 0));
+        assert!(ranked.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+        assert!(ranked.iter().any(|(entries, _)| entries == &vec![Entry::Observation(0), Entry::FinalAccusation(0)]));
+        assert!(ranked.iter().any(|(entries, _)| entries == &vec![Entry::Accusation(0), Entry::Accusation(1)]));
+
+        // A pair already does, so no set of three is needed
+        assert_eq!(rank_likely_mistakes(&gs, 3).unwrap(), ranked);
+    }
+
+    #[test]
+    fn test_get_index_subsets() {
+        assert_eq!(get_index_subsets(3, 2), vec![vec![0, 1], vec![0, 2], vec![1, 2]]);
+        assert_eq!(get_index_subsets(2, 3), Vec::<Vec<usize>>::new());
+    }
+}
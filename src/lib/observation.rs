@@ -0,0 +1,21 @@
+use crate::cards::Card;
+use crate::deduction::Fact;
+use crate::holder::Holder;
+
+/// Something seen outside of an accusation, like a player leaving the game
+/// and showing their hand, a card dropped face up, or an intrigue card
+/// forcing a card to be shown.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub player_index: usize,
+    pub card: Card,
+
+    /// Whether the player has the card, or doesn't have it
+    pub has: bool,
+}
+
+impl Observation {
+    pub fn get_fact(&self) -> Fact {
+        Fact::new(Holder::Player(self.player_index), self.card, self.has)
+    }
+}
@@ -1,6 +1,5 @@
-use std::collections::HashSet;
-
-use crate::cards::Card;
+use crate::card_set::CardSet;
+use crate::deck::Deck;
 
 /// Contains details about what we know / don't know about a 
 /// player's hand.
@@ -11,7 +10,7 @@ use crate::cards::Card;
 /// 
 /// String contains a nice user-readable name to differentiate between
 /// hands.
-
+//
 // Callers from outside my crate can't directly construct me
 // or exhaustively match on my fields!
 #[non_exhaustive]
@@ -19,8 +18,34 @@ use crate::cards::Card;
 pub struct PlayerHand {
     pub player_name: String,
 
-    pub must_have: HashSet<Card>,
-    pub must_not_have: HashSet<Card>
+    pub must_have: CardSet,
+    pub must_not_have: CardSet,
+
+    /// Groups of cards where the player must have at least
+    /// one card from each group, like when they show a card
+    /// we don't get to see.
+    #[serde(default)]
+    pub must_have_one_of: Vec<CardSet>,
+
+    /// Number of cards the player was dealt. None when the cards were
+    /// dealt evenly, with the rest left face up.
+    #[serde(default)]
+    pub hand_size: Option<usize>,
+
+    #[serde(default)]
+    pub kind: HandKind,
+}
+
+/// Who a hand was dealt to
+#[derive(Debug, Clone, Copy, Default, serde::Serialize, serde::Deserialize, PartialEq, Eq)]
+pub enum HandKind {
+    /// Someone playing the game
+    #[default]
+    Player,
+
+    /// A hand dealt to nobody, which responds to accusations but never takes a turn.
+    /// Used in two player and short handed games.
+    Dummy,
 }
 
 impl PlayerHand {
@@ -28,17 +53,44 @@ impl PlayerHand {
         PlayerHand { 
             player_name, 
 
-            must_have: HashSet::new(), 
-            must_not_have: HashSet::new(),
+            must_have: CardSet::new(), 
+            must_not_have: CardSet::new(),
+            must_have_one_of: Vec::new(),
+            hand_size: None,
+            kind: HandKind::Player,
         }
     }
 
-    pub fn is_complete(&self, expected_number_of_cards: usize) -> bool {
+    /// A hand which responds to accusations, but never takes a turn
+    pub fn new_dummy(player_name: String) -> PlayerHand {
+        PlayerHand {
+            kind: HandKind::Dummy,
+            ..PlayerHand::new(player_name)
+        }
+    }
+
+    pub fn is_dummy(&self) -> bool {
+        self.kind == HandKind::Dummy
+    }
+
+    /// The envelope is tracked like any other hand,
+    /// except it holds exactly one card of each category.
+    pub fn new_envelope() -> PlayerHand {
+        PlayerHand::new("Envelope".to_owned())
+    }
+
+    /// The hand, dealt hand_size cards
+    pub fn with_hand_size(mut self, hand_size: usize) -> PlayerHand {
+        self.hand_size = Some(hand_size);
+        self
+    }
+
+    pub fn is_complete(&self, expected_number_of_cards: usize, deck: &Deck) -> bool {
         if self.must_have.len() == expected_number_of_cards {
             return true;
         }
 
-        if self.must_not_have.len() == (18 - expected_number_of_cards - self.must_have.len()) {
+        if self.must_not_have.len() == (deck.get_number_of_cards_outside_envelope() - expected_number_of_cards - self.must_have.len()) {
             return true;
         }
         
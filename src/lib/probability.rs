@@ -0,0 +1,685 @@
+use std::collections::{HashMap, HashSet};
+
+use error_chain::bail;
+
+use crate::cards::*;
+use crate::game_state::GameState;
+use crate::holder::Holder;
+use crate::errors::*;
+
+/// Most players the deal counter can track hand slots for
+const MAX_PLAYERS: usize = 16;
+
+/// Most "has at least one of" constraints that can be undecided at once
+const MAX_OPEN_CLAUSES: usize = 128;
+
+/// Most categories the deal counter can track the envelope for
+const MAX_CATEGORIES: usize = 8;
+
+/// How many of the consistent deals put each card with each holder.
+#[derive(Debug, Clone)]
+pub struct CardProbabilities {
+    /// Number of deals consistent with everything we know
+    pub total_deals: u128,
+
+    /// Players first in seat order, then the envelope
+    pub holders: Vec<Holder>,
+
+    deal_counts: HashMap<Card, Vec<u128>>,
+}
+
+impl CardProbabilities {
+    /// Number of consistent deals where the holder has the card.
+    ///
+    /// Public cards are never held by anyone.
+    pub fn get_deal_count(&self, card: &Card, holder: Holder) -> u128 {
+        let holder_index = match self.holders.iter().position(|h| *h == holder) {
+            Some(idx) => idx,
+            None => return 0,
+        };
+
+        match self.deal_counts.get(card) {
+            Some(counts) => counts[holder_index],
+            None => 0,
+        }
+    }
+
+    pub fn get_probability(&self, card: &Card, holder: Holder) -> f64 {
+        self.get_deal_count(card, holder) as f64 / self.total_deals as f64
+    }
+}
+
+/// Counts every deal consistent with the game state, which is zero if the
+/// game contradicts itself.
+pub fn count_consistent_deals(gs: &GameState) -> Result<u128> {
+    let model = match DealModel::from_game_state(gs) {
+        Ok(model) => model,
+        Err(Error(ErrorKind::NoConsistentDeals, _)) => return Ok(0),
+        Err(e) => return Err(e),
+    };
+
+    let layers = model.count_forward();
+
+    Ok(layers[model.cards.len()].values().sum())
+}
+
+/// Counts every deal consistent with the game state and returns how
+/// likely each card is to be with each player or in the envelope.
+///
+/// A deal is consistent when it respects every player's must have and must
+/// not have, the hand sizes, the public cards, every accusation, and puts
+/// exactly one card of each category in the envelope.
+pub fn get_card_probabilities(gs: &GameState) -> Result<CardProbabilities> {
+    let model = DealModel::from_game_state(gs)?;
+
+    let layers = model.count_forward();
+
+    let total_deals: u128 = layers[model.cards.len()].values().sum();
+
+    if total_deals == 0 {
+        bail!(ErrorKind::NoConsistentDeals);
+    }
+
+    let completions = model.count_backward(&layers);
+
+    let mut deal_counts: HashMap<Card, Vec<u128>> = HashMap::new();
+
+    for (card_idx, card) in model.cards.iter().enumerate() {
+        let mut counts = vec![0; model.number_of_holders()];
+
+        for (state, deals_so_far) in layers[card_idx].iter() {
+            for (holder_idx, count) in counts.iter_mut().enumerate() {
+                if let Some(next_state) = model.transition(state, card_idx, holder_idx) {
+                    if let Some(deals_after) = completions[card_idx + 1].get(&next_state) {
+                        *count += deals_so_far * deals_after;
+                    }
+                }
+            }
+        }
+
+        deal_counts.insert(*card, counts);
+    }
+
+    let holders = (0..gs.player_hands.len())
+        .map(Holder::Player)
+        .chain(std::iter::once(Holder::Envelope))
+        .collect();
+
+    Ok(CardProbabilities { total_deals, holders, deal_counts })
+}
+
+
+/// One literal of a "at least one of" clause.
+/// True when the card is (or is not, if has is false) with the holder.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Literal {
+    card_idx: usize,
+    holder_idx: usize,
+    has: bool,
+}
+
+/// Partial deal after some prefix of the cards has been handed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct DealState {
+    /// Open slots left in each player's hand
+    slots: [u8; MAX_PLAYERS],
+
+    /// Categories that already have their envelope card
+    envelope: u8,
+
+    /// Open clauses which are already satisfied
+    satisfied: u128,
+}
+
+/// The game boiled down to what the deal counter needs.
+///
+/// Holders are indexed with players first and the envelope last.
+/// Cards are sorted so each category is contiguous.
+struct DealModel {
+    cards: Vec<Card>,
+    number_of_players: usize,
+    hand_sizes: Vec<u8>,
+
+    /// allowed[card][holder]
+    allowed: Vec<Vec<bool>>,
+
+    /// Literals that mention each card, with the bit of their clause
+    literals_by_card: Vec<Vec<(Literal, usize)>>,
+
+    /// Clause bits that must be satisfied once each card is dealt
+    closing_by_card: Vec<u128>,
+
+    /// Category of each card
+    categories: Vec<usize>,
+
+    /// Category of each card which is the last card of its category
+    last_of_category: Vec<Option<usize>>,
+}
+
+impl DealModel {
+    fn from_game_state(gs: &GameState) -> Result<DealModel> {
+        let number_of_players = gs.player_hands.len();
+
+        if number_of_players > MAX_PLAYERS {
+            bail!(ErrorKind::UnsupportedGameSize(
+                format!("{} players, while the limit is {}", number_of_players, MAX_PLAYERS)));
+        }
+
+        if gs.deck.get_number_of_categories() > MAX_CATEGORIES {
+            bail!(ErrorKind::UnsupportedGameSize(
+                format!("{} categories, while the limit is {}", gs.deck.get_number_of_categories(), MAX_CATEGORIES)));
+        }
+
+        let hand_sizes: Vec<usize> = (0..number_of_players).map(|player_idx| gs.get_hand_size(player_idx)).collect();
+
+        let mut cards: Vec<Card> = (&gs.deck.get_all_cards() - &gs.public_cards).into_iter().collect();
+        cards.sort();
+
+        if cards.len() != hand_sizes.iter().sum::<usize>() + gs.deck.get_number_of_categories() {
+            // Cards left over which nobody can hold
+            bail!(ErrorKind::NoConsistentDeals);
+        }
+
+        let card_index: HashMap<&Card, usize> = cards.iter()
+            .enumerate()
+            .map(|(idx, card)| (card, idx))
+            .collect();
+
+        let mut allowed = vec![vec![true; number_of_players + 1]; cards.len()];
+
+        // Only the holder of a must have may hold that card
+        let force = |allowed: &mut Vec<Vec<bool>>, card: &Card, holder_idx: usize| -> Result<()> {
+            match card_index.get(&card) {
+                Some(&card_idx) => {
+                    for (other_idx, is_allowed) in allowed[card_idx].iter_mut().enumerate() {
+                        if other_idx != holder_idx {
+                            *is_allowed = false;
+                        }
+                    }
+                    Ok(())
+                }
+                // Public cards are not in anyone's hand
+                None => bail!(ErrorKind::NoConsistentDeals),
+            }
+        };
+
+        for (player_idx, hand) in gs.player_hands.iter().enumerate() {
+            for card in hand.must_have.iter() {
+                force(&mut allowed, &card, player_idx)?;
+            }
+
+            for card in hand.must_not_have.iter() {
+                if let Some(&card_idx) = card_index.get(&card) {
+                    allowed[card_idx][player_idx] = false;
+                }
+            }
+        }
+
+        for observation in gs.observations.iter() {
+            if observation.has {
+                force(&mut allowed, &observation.card, observation.player_index)?;
+            } else if let Some(&card_idx) = card_index.get(&observation.card) {
+                allowed[card_idx][observation.player_index] = false;
+            }
+        }
+
+        let mut clauses: HashSet<Vec<Literal>> = HashSet::new();
+
+        for (player_idx, hand) in gs.player_hands.iter().enumerate() {
+            for cards in hand.must_have_one_of.iter() {
+                let clause = cards.iter()
+                    .filter_map(|card| card_index.get(&card))
+                    .map(|&card_idx| Literal { card_idx, holder_idx: player_idx, has: true })
+                    .collect();
+
+                clauses.insert(clause);
+            }
+        }
+
+        let envelope_idx = number_of_players;
+
+        for card in gs.envelope.must_have.iter() {
+            force(&mut allowed, &card, envelope_idx)?;
+        }
+
+        for card in gs.envelope.must_not_have.iter() {
+            if let Some(&card_idx) = card_index.get(&card) {
+                allowed[card_idx][envelope_idx] = false;
+            }
+        }
+
+        for acc in gs.accusations.iter() {
+            let accused_cards = &acc.accused_cards;
+
+            // Players who were asked and could not respond
+            for player_idx in gs.get_players_without_cards(acc) {
+                for card in accused_cards.iter() {
+                    if let Some(&card_idx) = card_index.get(&card) {
+                        allowed[card_idx][player_idx] = false;
+                    }
+                }
+            }
+
+            // Public cards aren't in any hand
+            for clause in gs.get_possible_responder_clauses(acc) {
+                if clause.iter().any(|fact| !fact.has && !card_index.contains_key(&fact.card)) {
+                    continue;
+                }
+
+                clauses.insert(clause.iter()
+                    .filter_map(|fact| match (fact.holder, card_index.get(&fact.card)) {
+                        (Holder::Player(holder_idx), Some(&card_idx)) => Some(Literal { card_idx, holder_idx, has: fact.has }),
+                        _ => None,
+                    })
+                    .collect());
+            }
+
+            for response in gs.get_responses(acc) {
+                let responding_player_idx = response.player_index;
+
+                match &response.card_shown {
+                    Some(card_shown) => force(&mut allowed, card_shown, responding_player_idx)?,
+                    None => {
+                        let clause = accused_cards.iter()
+                            .filter_map(|card| card_index.get(&card))
+                            .map(|&card_idx| Literal { card_idx, holder_idx: responding_player_idx, has: true })
+                            .collect();
+
+                        clauses.insert(clause);
+                    }
+                }
+            }
+        }
+
+        // Wrong final accusations, a named card is in someone's hand. If one is
+        // face up that's already true.
+        for final_accusation in gs.final_accusations.iter() {
+            let accused_card_indices: Option<Vec<usize>> = final_accusation.accused_cards.iter()
+                .map(|card| card_index.get(&card).cloned())
+                .collect();
+
+            if let Some(accused_card_indices) = accused_card_indices {
+                let clause = accused_card_indices.into_iter()
+                    .flat_map(|card_idx| (0..number_of_players)
+                        .map(move |holder_idx| Literal { card_idx, holder_idx, has: true }))
+                    .collect();
+
+                clauses.insert(clause);
+            }
+        }
+
+        // Drop literals that can never be true and clauses that always are
+        let mut simplified_clauses: Vec<Vec<Literal>> = Vec::new();
+
+        for clause in clauses {
+            let mut simplified: Vec<Literal> = Vec::new();
+            let mut always_true = false;
+
+            for literal in clause {
+                let is_allowed = allowed[literal.card_idx][literal.holder_idx];
+
+                if !literal.has && !is_allowed {
+                    always_true = true;
+                    break;
+                }
+
+                if is_allowed {
+                    simplified.push(literal);
+                }
+            }
+
+            if always_true {
+                continue;
+            }
+
+            if simplified.is_empty() {
+                bail!(ErrorKind::NoConsistentDeals);
+            }
+
+            simplified.sort();
+            simplified_clauses.push(simplified);
+        }
+
+        // Give each clause a bit, reusing bits of clauses that are already decided
+        simplified_clauses.sort_by_key(|clause| clause[0].card_idx);
+
+        let mut literals_by_card: Vec<Vec<(Literal, usize)>> = vec![Vec::new(); cards.len()];
+        let mut closing_by_card: Vec<u128> = vec![0; cards.len()];
+        let mut bit_free_after: Vec<usize> = Vec::new();
+
+        for clause in simplified_clauses {
+            let opens = clause[0].card_idx;
+            let closes = clause.last().unwrap().card_idx;
+
+            let bit = match bit_free_after.iter().position(|&free_after| free_after < opens) {
+                Some(bit) => {
+                    bit_free_after[bit] = closes;
+                    bit
+                }
+                None => {
+                    if bit_free_after.len() == MAX_OPEN_CLAUSES {
+                        bail!(ErrorKind::UnsupportedGameSize(
+                            format!("more than {} undecided accusations", MAX_OPEN_CLAUSES)));
+                    }
+                    bit_free_after.push(closes);
+                    bit_free_after.len() - 1
+                }
+            };
+
+            closing_by_card[closes] |= 1 << bit;
+
+            for literal in clause {
+                literals_by_card[literal.card_idx].push((literal, bit));
+            }
+        }
+
+        let categories: Vec<usize> = cards.iter().map(|card| gs.deck.get_category_index(card)).collect();
+
+        let mut last_of_category = vec![None; cards.len()];
+        for (card_idx, category) in categories.iter().enumerate() {
+            let is_last = categories.get(card_idx + 1)
+                .map(|next| next != category)
+                .unwrap_or(true);
+
+            if is_last {
+                last_of_category[card_idx] = Some(*category);
+            }
+        }
+
+        if !cards.iter().all(|card| allowed[card_index[card]].iter().any(|a| *a)) {
+            bail!(ErrorKind::NoConsistentDeals);
+        }
+
+        Ok(DealModel {
+            cards,
+            number_of_players,
+            hand_sizes: hand_sizes.into_iter().map(|hand_size| hand_size as u8).collect(),
+            allowed,
+            literals_by_card,
+            closing_by_card,
+            categories,
+            last_of_category,
+        })
+    }
+
+    fn number_of_holders(&self) -> usize {
+        self.number_of_players + 1
+    }
+
+    fn initial_state(&self) -> DealState {
+        let mut slots = [0; MAX_PLAYERS];
+        slots[..self.number_of_players].copy_from_slice(&self.hand_sizes);
+
+        DealState { slots, envelope: 0, satisfied: 0 }
+    }
+
+    /// Deals the card to the holder, or None if that breaks a rule.
+    fn transition(&self, state: &DealState, card_idx: usize, holder_idx: usize) -> Option<DealState> {
+        if !self.allowed[card_idx][holder_idx] {
+            return None;
+        }
+
+        let mut next = *state;
+        let category_bit = 1 << self.categories[card_idx];
+
+        if holder_idx == self.number_of_players {
+            if next.envelope & category_bit != 0 {
+                return None;
+            }
+            next.envelope |= category_bit;
+        } else {
+            if next.slots[holder_idx] == 0 {
+                return None;
+            }
+            next.slots[holder_idx] -= 1;
+        }
+
+        for (literal, bit) in self.literals_by_card[card_idx].iter() {
+            if (literal.holder_idx == holder_idx) == literal.has {
+                next.satisfied |= 1 << bit;
+            }
+        }
+
+        // Clauses whose cards have all been dealt must be satisfied by now
+        let closing = self.closing_by_card[card_idx];
+        if next.satisfied & closing != closing {
+            return None;
+        }
+        next.satisfied &= !closing;
+
+        // Every category needs a card in the envelope
+        if let Some(category) = self.last_of_category[card_idx] {
+            if next.envelope & (1 << category) == 0 {
+                return None;
+            }
+        }
+
+        Some(next)
+    }
+
+    /// Number of ways to reach each partial deal, for every prefix of the cards
+    fn count_forward(&self) -> Vec<HashMap<DealState, u128>> {
+        let mut layers: Vec<HashMap<DealState, u128>> = Vec::with_capacity(self.cards.len() + 1);
+        layers.push(HashMap::from([(self.initial_state(), 1)]));
+
+        for card_idx in 0..self.cards.len() {
+            let mut next_layer: HashMap<DealState, u128> = HashMap::new();
+
+            for (state, count) in layers[card_idx].iter() {
+                for holder_idx in 0..self.number_of_holders() {
+                    if let Some(next_state) = self.transition(state, card_idx, holder_idx) {
+                        *next_layer.entry(next_state).or_insert(0) += count;
+                    }
+                }
+            }
+
+            layers.push(next_layer);
+        }
+
+        layers
+    }
+
+    /// Number of ways to finish the deal from each reachable partial deal
+    fn count_backward(&self, layers: &[HashMap<DealState, u128>]) -> Vec<HashMap<DealState, u128>> {
+        let mut completions: Vec<HashMap<DealState, u128>> = vec![HashMap::new(); layers.len()];
+
+        completions[self.cards.len()] = layers[self.cards.len()].keys()
+            .map(|state| (*state, 1))
+            .collect();
+
+        for card_idx in (0..self.cards.len()).rev() {
+            let mut current: HashMap<DealState, u128> = HashMap::new();
+
+            for state in layers[card_idx].keys() {
+                let count: u128 = (0..self.number_of_holders())
+                    .filter_map(|holder_idx| self.transition(state, card_idx, holder_idx))
+                    .filter_map(|next_state| completions[card_idx + 1].get(&next_state))
+                    .sum();
+
+                if count > 0 {
+                    current.insert(*state, count);
+                }
+            }
+
+            completions[card_idx] = current;
+        }
+
+        completions
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::card_set::CardSet;
+    use crate::deck::{Deck, tests::card};
+    use crate::accusation::Accusation;
+    use crate::player_hand::PlayerHand;
+
+    use super::*;
+
+    fn new_game_state(number_of_players: usize) -> GameState {
+        GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands: (0..number_of_players)
+                .map(|idx| PlayerHand::new(format!("p{}", idx + 1)))
+                .collect(),
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        }
+    }
+
+    fn assert_probability(probabilities: &CardProbabilities, card: Card, holder: Holder, expected: f64) {
+        let actual = probabilities.get_probability(&card, holder);
+        assert!((actual - expected).abs() < 1e-9, "{:?} with {:?} was {}, expected {}", card, holder, actual, expected);
+    }
+
+    #[test]
+    fn test_card_probabilities_no_information() {
+        let gs = new_game_state(3);
+
+        let probabilities = get_card_probabilities(&gs).unwrap();
+
+        // 18 cards, 6 to each player, one of each category to the envelope
+        assert_probability(&probabilities, card("Kitchen"), Holder::Envelope, 1.0 / 9.0);
+        assert_probability(&probabilities, card("Rope"), Holder::Envelope, 1.0 / 6.0);
+        assert_probability(&probabilities, card("Kitchen"), Holder::Player(1), 8.0 / 27.0);
+        assert_probability(&probabilities, card("Plum"), Holder::Player(2), 5.0 / 18.0);
+    }
+
+    #[test]
+    fn test_card_probabilities_must_have_is_certain() {
+        let mut gs = new_game_state(4);
+        gs.public_cards.insert(card("Hall"));
+        gs.public_cards.insert(card("Study"));
+
+        gs.player_hands[0].must_have.insert(card("Pipe"));
+
+        let probabilities = get_card_probabilities(&gs).unwrap();
+
+        assert_probability(&probabilities, card("Pipe"), Holder::Player(0), 1.0);
+        assert_probability(&probabilities, card("Pipe"), Holder::Envelope, 0.0);
+        assert_probability(&probabilities, card("Rope"), Holder::Envelope, 1.0 / 5.0);
+
+        // Public cards are nowhere
+        assert_eq!(probabilities.get_deal_count(&card("Hall"), Holder::Envelope), 0);
+        assert_eq!(probabilities.get_deal_count(&card("Hall"), Holder::Player(2)), 0);
+    }
+
+    #[test]
+    fn test_card_probabilities_matches_brute_force() {
+        let mut gs = new_game_state(3);
+
+        let known_hands = [
+            vec![
+                card("Kitchen"), card("Ballroom"), card("Conservatory"),
+                card("Rope"), card("Candlestick"), card("Mustard"),
+            ],
+            vec![
+                card("Dining"), card("Lounge"),
+                card("Knife"), card("Plum"),
+            ],
+            vec![
+                card("Hall"), card("Study"),
+                card("Green"), card("Peacock"),
+            ],
+        ];
+
+        for (hand, cards) in gs.player_hands.iter_mut().zip(known_hands.iter()) {
+            hand.must_have.extend(cards.iter().cloned());
+        }
+
+        // p2 could not respond, p3 showed a card to p1 we didn't see
+        gs.accusations.push(Accusation {
+            accuser_player_index: 0,
+            accused_cards: vec![card("Library"), card("Pipe"), card("Scarlet")],
+            responding_player_index: Some(2),
+            card_shown: None,
+            responses: Vec::new(),
+            asked_players: None,
+            possible_responders: Vec::new(),
+        });
+
+        let unknown_cards = [
+            card("Library"), card("Billiard"),
+            card("Pipe"), card("Pistol"), card("Wrench"),
+            card("Scarlet"), card("White"),
+        ];
+
+        // Try every way to hand the unknown cards to p2, p3 and the envelope
+        let holders = [Holder::Player(1), Holder::Player(2), Holder::Envelope];
+        let mut expected_counts: HashMap<(Card, Holder), u128> = HashMap::new();
+        let mut expected_total = 0;
+
+        for deal_number in 0..holders.len().pow(unknown_cards.len() as u32) {
+            let mut rest = deal_number;
+            let deal: Vec<Holder> = unknown_cards.iter().map(|_| {
+                let holder = holders[rest % holders.len()];
+                rest /= holders.len();
+                holder
+            }).collect();
+
+            let count_for = |holder: Holder| deal.iter().filter(|h| **h == holder).count();
+            let holder_of = |card: &Card| deal[unknown_cards.iter().position(|c| c == card).unwrap()];
+
+            let categories_in_envelope: HashSet<usize> = unknown_cards.iter().zip(deal.iter())
+                .filter(|(_, holder)| **holder == Holder::Envelope)
+                .map(|(card, _)| Deck::classic().get_category_index(card))
+                .collect();
+
+            let accused = [card("Library"), card("Pipe"), card("Scarlet")];
+
+            if count_for(Holder::Player(1)) != 2 || count_for(Holder::Player(2)) != 2
+                || categories_in_envelope.len() != 3
+                || accused.iter().any(|card| holder_of(card) == Holder::Player(1))
+                || !accused.iter().any(|card| holder_of(card) == Holder::Player(2)) {
+                continue;
+            }
+
+            expected_total += 1;
+            for (card, holder) in unknown_cards.iter().zip(deal.iter()) {
+                *expected_counts.entry((*card, *holder)).or_insert(0) += 1;
+            }
+        }
+
+        let probabilities = get_card_probabilities(&gs).unwrap();
+
+        assert_eq!(probabilities.total_deals, expected_total);
+
+        for card in unknown_cards.iter() {
+            for holder in holders.iter() {
+                assert_eq!(
+                    probabilities.get_deal_count(card, *holder),
+                    *expected_counts.get(&(*card, *holder)).unwrap_or(&0),
+                    "{:?} with {:?}", card, holder);
+            }
+        }
+    }
+
+    #[test]
+    fn test_card_probabilities_contradiction_has_no_deals() {
+        let mut gs = new_game_state(3);
+
+        gs.player_hands[1].must_not_have.extend([
+            card("Library"), card("Pipe"), card("Scarlet")]);
+
+        gs.accusations.push(Accusation {
+            accuser_player_index: 0,
+            accused_cards: vec![card("Library"), card("Pipe"), card("Scarlet")],
+            responding_player_index: Some(1),
+            card_shown: None,
+            responses: Vec::new(),
+            asked_players: None,
+            possible_responders: Vec::new(),
+        });
+
+        assert!(get_card_probabilities(&gs).is_err());
+    }
+}
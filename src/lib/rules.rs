@@ -0,0 +1,31 @@
+/// House rules a game is played with, which change what accusations tell us.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+pub struct GameRules {
+    #[serde(default)]
+    pub response_rule: ResponseRule,
+
+    #[serde(default)]
+    pub play_direction: PlayDirection,
+}
+
+/// Who shows a card when an accusation is made
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseRule {
+    /// Players are asked in turn, and the first who can shows a card
+    #[default]
+    FirstCanShow,
+
+    /// Every player who can shows a card
+    EveryoneShows,
+}
+
+/// Which way turns, and asking players to respond, go around the table
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlayDirection {
+    /// To the left, in the order players were entered
+    #[default]
+    Clockwise,
+
+    /// To the right, in the reverse of the order players were entered
+    Counterclockwise,
+}
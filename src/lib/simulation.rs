@@ -0,0 +1,132 @@
+//! Simulated games for tests and benchmarks, dealt and played out from a
+//! seed so they're the same every run.
+
+use crate::accusation::Accusation;
+use crate::audit::Reveal;
+use crate::card_set::CardSet;
+use crate::cards::Card;
+use crate::deck::Deck;
+use crate::game_state::GameState;
+use crate::player_hand::PlayerHand;
+use crate::solver::get_responding_players;
+
+/// Deals the classic deck from a seed, then plays out random accusations
+/// as seen by player 0. The deal is kept as the game's reveal.
+pub fn simulate_game(number_of_players: usize, seed: u64, number_of_accusations: usize) -> GameState {
+    simulate_game_with_deck(Deck::classic(), number_of_players, seed, number_of_accusations, false)
+}
+
+/// Like simulate_game, except with any deck, and if deal_every_card is set
+/// no cards are left face up, so the first players dealt to get a card more.
+pub fn simulate_game_with_deck(card_deck: Deck, number_of_players: usize, seed: u64,
+    number_of_accusations: usize, deal_every_card: bool) -> GameState {
+
+    let mut seed = seed;
+    let mut random = |limit: usize| -> usize {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        ((seed >> 33) as usize) % limit
+    };
+
+    let mut deck: Vec<Card> = card_deck.get_all_cards().into_iter().collect();
+    for idx in (1..deck.len()).rev() {
+        deck.swap(idx, random(idx + 1));
+    }
+
+    let mut envelope: Vec<Card> = Vec::new();
+    for category_index in 0..card_deck.get_number_of_categories() {
+        let position = deck.iter().position(|card| card_deck.get_category_index(card) == category_index).unwrap();
+        envelope.push(deck.remove(position));
+    }
+
+    let number_of_public_cards = if deal_every_card { 0 } else { deck.len() % number_of_players };
+    let public_cards: CardSet = deck.split_off(deck.len() - number_of_public_cards).into_iter().collect();
+
+    let mut hands: Vec<CardSet> = vec![CardSet::new(); number_of_players];
+    for (idx, card) in deck.into_iter().enumerate() {
+        hands[idx % number_of_players].insert(card);
+    }
+
+    let mut player_hands: Vec<PlayerHand> = (0..number_of_players)
+        .map(|idx| PlayerHand::new(format!("p{}", idx + 1)))
+        .collect();
+    player_hands[0].must_have = hands[0];
+
+    if deal_every_card {
+        for (player_hand, hand) in player_hands.iter_mut().zip(hands.iter()) {
+            player_hand.hand_size = Some(hand.len());
+        }
+    }
+
+    let categories: Vec<Vec<Card>> = (0..card_deck.get_number_of_categories())
+        .map(|category_index| card_deck.get_all_cards_in_category(category_index).into_iter().collect())
+        .collect();
+
+    let mut gs = GameState {
+        deck: card_deck,
+        public_cards,
+        player_hands,
+        self_index: Some(0),
+        envelope: PlayerHand::new_envelope(),
+        accusations: Vec::new(),
+        final_accusations: Vec::new(),
+        observations: Vec::new(),
+        deductions: Vec::new(),
+        rules: Default::default(),
+        reveal: Some(Reveal {
+            envelope: envelope.into_iter().collect(),
+            player_hands: Some(hands.clone()),
+        }),
+    };
+
+    for turn in 0..number_of_accusations {
+        let accuser_player_index = turn % number_of_players;
+
+        let accused_cards: Vec<Card> = categories.iter()
+            .map(|category_cards| category_cards[random(category_cards.len())])
+            .collect();
+
+        let responding_player_index = get_responding_players(accuser_player_index, None, number_of_players)
+            .into_iter()
+            .find(|idx| accused_cards.iter().any(|card| hands[*idx].contains(card)));
+
+        let card_shown = match responding_player_index {
+            Some(idx) if idx == 0 || accuser_player_index == 0 => 
+                accused_cards.iter().find(|card| hands[idx].contains(card)).copied(),
+            _ => None,
+        };
+
+        gs.accusations.push(Accusation {
+            accuser_player_index,
+            accused_cards,
+            responding_player_index,
+            card_shown,
+            responses: Vec::new(),
+            asked_players: None,
+            possible_responders: Vec::new(),
+        });
+    }
+
+    gs
+}
+
+/// Forgets who responded to some accusations, leaving them as one of the
+/// responder and the next player asked.
+pub fn miss_responders(gs: &mut GameState) {
+    let number_of_players = gs.player_hands.len();
+
+    for acc in gs.accusations.iter_mut().step_by(2) {
+        let responder = match acc.responding_player_index {
+            Some(responder) if Some(responder) != gs.self_index && Some(acc.accuser_player_index) != gs.self_index => responder,
+            _ => continue,
+        };
+
+        let next_player = (responder + 1) % number_of_players;
+
+        acc.responding_player_index = None;
+        acc.possible_responders = vec![responder];
+
+        if next_player != acc.accuser_player_index {
+            acc.possible_responders.push(next_player);
+        }
+    }
+}
@@ -1,262 +1,1782 @@
-use std::collections::HashSet;
+use crate::card_set::CardSet;
+
+use error_chain::bail;
 
 use crate::game_state::GameState;
+use crate::holder::Holder;
+use crate::deduction::{Deduction, Fact, Rule};
 use crate::cards::*;
 use crate::errors::*;
 
 
-/// Applies logicial consequences that must be true.
-/// 
-/// GameState must be valid in order to run propagate_state
-pub fn propagate_state(gs: &mut GameState) -> Result<()>{
+/// Applies logicial consequences that must be true.
+/// 
+/// Every fact learned is recorded in the GameState's deductions,
+/// along with the rule and facts used to deduce it.
+/// 
+/// GameState must be valid in order to run propagate_state
+/// 
+/// The pigeonhole rule checks every group of the hands and envelope
+/// categories which could still take a card, so fails with
+/// TooManyOpenHolders if there are more than 16 of them.
+pub fn propagate_state(gs: &mut GameState) -> Result<()>{
+
+    // -> Must have / Does not haves
+    for acc_idx in 0..gs.accusations.len() {
+        apply_accusation_response(gs, acc_idx);
+    }
+
+    // -> Envelope does not haves
+    apply_public_cards(gs);
+
+    // -> Must have / Does not haves
+    apply_observations(gs);
+
+
+    // Since the next blocks might impact each other,
+    // we keep running them until no more changes occur.
+    let mut changed = true;
+    while changed {
+        changed = false;
+
+        // Must Haves -> Does not haves
+        for holder in gs.get_holders() {
+            for card in gs.get_hand(holder).must_have {
+                changed |= apply_holder_has_card(gs, holder, &card);
+            }
+        }
+
+        // Does not haves -> Must have
+        for card in &gs.deck.get_all_cards() - &gs.public_cards {
+            changed |= apply_only_place_left(gs, &card);
+        }
+
+        for category_index in 0..gs.deck.get_number_of_categories() {
+            changed |= apply_envelope_category(gs, category_index);
+        }
+
+        // Unknown Accusation -> Must have one of
+        for acc_idx in 0..gs.accusations.len() {
+            changed |= remember_unseen_card(gs, acc_idx);
+        }
+
+        // Does not haves -> Does not haves / Must have one of
+        for acc_idx in 0..gs.accusations.len() {
+            changed |= apply_possible_responders(gs, acc_idx);
+        }
+
+        // Envelope must haves -> Envelope does not have
+        for final_idx in 0..gs.final_accusations.len() {
+            changed |= apply_wrong_final_accusation(gs, final_idx);
+        }
+
+        // Must Have -> Does not have
+        for player_idx in 0..gs.player_hands.len() {
+            changed |= apply_hand_is_full(gs, player_idx);
+        }
+
+        // Does not haves -> Must have
+        for player_idx in 0..gs.player_hands.len() {
+            changed |= apply_fills_rest_of_hand(gs, player_idx);
+        }
+
+        // Must have one of -> Must have / Does not have
+        for player_idx in 0..gs.player_hands.len() {
+            changed |= apply_must_have_one_of(gs, player_idx);
+        }
+
+        changed |= apply_open_slot_counting(gs)?;
+    }
+
+    Ok(())
+}
+
+/// If a player shows a card, they must have that card, and everyone
+/// asked who couldn't respond has none of the cards.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_accusation_response(gs: &mut GameState, acc_idx: usize) -> bool {
+    let acc = &gs.accusations[acc_idx];
+
+    let mut deductions: Vec<Deduction> = Vec::new();
+
+    for response in gs.get_responses(acc) {
+        if let Some(card_shown) = response.card_shown {
+            deductions.push(Deduction::new(
+                Fact::new(Holder::Player(response.player_index), card_shown, true), 
+                Rule::ShowedCard
+            ).from_accusation(acc_idx));
+        }
+    }
+
+    let players_who_did_not_have_cards = gs.get_players_without_cards(acc);
+
+    for player_who_did_not_have_card_index in players_who_did_not_have_cards {
+        for card in acc.accused_cards.clone() {
+            deductions.push(Deduction::new(
+                Fact::new(Holder::Player(player_who_did_not_have_card_index), card, false), 
+                Rule::CouldNotRespond
+            ).from_accusation(acc_idx));
+        }
+    }
+
+    add_deductions(gs, deductions)
+}
+
+/// Whatever was seen outside of accusations is known
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_observations(gs: &mut GameState) -> bool {
+    let deductions: Vec<Deduction> = gs.observations.iter()
+        .map(|observation| Deduction::new(observation.get_fact(), Rule::Observed))
+        .collect();
+
+    add_deductions(gs, deductions)
+}
+
+/// Public cards are face up, so they can't be in the envelope
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_public_cards(gs: &mut GameState) -> bool {
+    let deductions: Vec<Deduction> = gs.public_cards.iter()
+        .map(|card| Deduction::new(Fact::new(Holder::Envelope, card, false), Rule::PublicCard))
+        .collect();
+
+    add_deductions(gs, deductions)
+}
+
+/// If someone (or the envelope) must have a card, everyone else must not have that card
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_holder_has_card(gs: &mut GameState, owner: Holder, card: &Card) -> bool {
+    let deductions: Vec<Deduction> = gs.get_holders()
+        .into_iter()
+        .filter(|holder| *holder != owner && !gs.get_hand(*holder).must_have.contains(card))
+        .map(|holder| Deduction::new(Fact::new(holder, *card, false), Rule::AnotherHolderHasIt)
+            .because(vec![Fact::new(owner, *card, true)]))
+        .collect();
+
+    add_deductions(gs, deductions)
+}
+
+/// Every card is somewhere, so if only one player (or the envelope)
+/// could have it, they must have it.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_only_place_left(gs: &mut GameState, card: &Card) -> bool {
+    if gs.public_cards.contains(card) {
+        return false;
+    }
+
+    let (could_have, could_not_have): (Vec<Holder>, Vec<Holder>) = gs.get_holders()
+        .into_iter()
+        .partition(|holder| !gs.get_hand(*holder).must_not_have.contains(card));
+
+    if let [only_holder] = could_have[..] {
+        let premises = could_not_have.into_iter()
+            .map(|holder| Fact::new(holder, *card, false))
+            .collect();
+
+        return gs.add_deduction(Deduction::new(Fact::new(only_holder, *card, true), Rule::OnlyPlaceLeft)
+            .because(premises));
+    }
+
+    false
+}
+
+/// The envelope holds exactly one card of each category, so once we know it
+/// the rest of the category is in players' hands, and if only one card of a
+/// category could be in the envelope, it must be.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_envelope_category(gs: &mut GameState, category_index: usize) -> bool {
+    let category_cards = gs.deck.get_all_cards_in_category(category_index);
+
+    let mut deductions: Vec<Deduction> = Vec::new();
+
+    if let Some(envelope_card) = category_cards.intersection(&gs.envelope.must_have).iter().next() {
+        for card in &category_cards - &gs.envelope.must_have {
+            deductions.push(Deduction::new(Fact::new(Holder::Envelope, card, false), Rule::EnvelopeHasCategory)
+                .because(vec![Fact::new(Holder::Envelope, envelope_card, true)]));
+        }
+
+        return add_deductions(gs, deductions);
+    }
+
+    let could_be_in_envelope: CardSet = &category_cards - &gs.envelope.must_not_have;
+
+    if could_be_in_envelope.len() == 1 {
+        let premises: Vec<Fact> = (&category_cards - &could_be_in_envelope).into_iter()
+            .map(|card| Fact::new(Holder::Envelope, card, false))
+            .collect();
+
+        for card in could_be_in_envelope {
+            deductions.push(Deduction::new(Fact::new(Holder::Envelope, card, true), Rule::LastOfCategory)
+                .because(premises.clone()));
+        }
+    }
+
+    add_deductions(gs, deductions)
+}
+
+/// A wrong final accusation named at least one card that isn't in the
+/// envelope, so once the envelope has all the others, the last can't be.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_wrong_final_accusation(gs: &mut GameState, final_idx: usize) -> bool {
+    let accused_cards = &gs.final_accusations[final_idx].accused_cards;
+
+    let (in_envelope, could_be_wrong): (Vec<Card>, Vec<Card>) = accused_cards.iter()
+        .partition(|card| gs.envelope.must_have.contains(card));
+
+    if let [wrong_card] = could_be_wrong[..] {
+        let premises = in_envelope.into_iter()
+            .map(|card| Fact::new(Holder::Envelope, card, true))
+            .collect();
+
+        return gs.add_deduction(Deduction::new(Fact::new(Holder::Envelope, wrong_card, false), Rule::WrongFinalAccusation)
+            .because(premises));
+    }
+
+    false
+}
+
+/// If a player showed a card we didn't see, they must have at least one
+/// of the three cards. We remember that, so it can be narrowed down later.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn remember_unseen_card(gs: &mut GameState, acc_idx: usize) -> bool {
+    let acc = &gs.accusations[acc_idx];
+
+    let potentially_shown_cards: CardSet = acc.accused_cards.clone().into_iter().collect();
+
+    let mut changed = false;
+
+    // Only responses where we didn't see the card
+    for response in gs.get_responses(acc) {
+        if response.card_shown.is_some() {
+            continue;
+        }
+
+        let responding_player_hand = gs.player_hands.get_mut(response.player_index).unwrap();
+
+        if !potentially_shown_cards.is_disjoint(&responding_player_hand.must_have) {
+            // At least one of our potential cards we already know they have, no new info
+            continue;
+        }
+
+        if responding_player_hand.must_have_one_of.iter().any(|cards| cards.is_subset(&potentially_shown_cards)) {
+            // Already know this, or something more specific
+            continue;
+        }
+
+        responding_player_hand.must_have_one_of.push(potentially_shown_cards);
+        changed = true;
+    }
+
+    changed
+}
+
+/// When we missed who responded, possible responders with none of the cards
+/// couldn't have responded. Everyone asked before the first one left
+/// couldn't respond either, and if only one is left, they showed a card.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_possible_responders(gs: &mut GameState, acc_idx: usize) -> bool {
+    let acc = &gs.accusations[acc_idx];
+
+    if acc.possible_responders.is_empty() {
+        return false;
+    }
+
+    let asked_players = gs.get_asked_players(acc);
+
+    let has_none_of_cards = |player_idx: usize| acc.accused_cards.iter()
+        .all(|card| gs.player_hands[player_idx].must_not_have.contains(card));
+
+    let remaining_responders: Vec<usize> = asked_players.iter()
+        .copied()
+        .filter(|player_idx| acc.possible_responders.contains(player_idx) && !has_none_of_cards(*player_idx))
+        .collect();
+
+    let first_responder = match remaining_responders.first() {
+        Some(first_responder) => *first_responder,
+        // Contradiction, left for verify_state
+        None => return false,
+    };
+
+    // Why the possible responders before them couldn't have responded
+    let premises: Vec<Fact> = asked_players.iter()
+        .take_while(|player_idx| **player_idx != first_responder)
+        .filter(|player_idx| acc.possible_responders.contains(player_idx))
+        .flat_map(|player_idx| acc.accused_cards.iter()
+            .map(|card| Fact::new(Holder::Player(*player_idx), *card, false)))
+        .collect();
+
+    let deductions: Vec<Deduction> = asked_players.iter()
+        .take_while(|player_idx| **player_idx != first_responder)
+        .flat_map(|player_idx| acc.accused_cards.iter()
+            .map(|card| Deduction::new(Fact::new(Holder::Player(*player_idx), *card, false), Rule::CouldNotRespond)
+                .from_accusation(acc_idx)
+                .because(premises.clone())))
+        .collect();
+
+    let mut changed = add_deductions(gs, deductions);
+
+    if remaining_responders.len() == 1 {
+        let potentially_shown_cards: CardSet = gs.accusations[acc_idx].accused_cards.iter().copied().collect();
+        let responder_hand = &mut gs.player_hands[first_responder];
+
+        let already_known = !potentially_shown_cards.is_disjoint(&responder_hand.must_have)
+            || responder_hand.must_have_one_of.iter().any(|cards| cards.is_subset(&potentially_shown_cards));
+
+        if !already_known {
+            responder_hand.must_have_one_of.push(potentially_shown_cards);
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// If a player's must have is complete, then finish completing their
+/// must not have with every other card.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_hand_is_full(gs: &mut GameState, player_idx: usize) -> bool {
+    let hand_size = gs.get_hand_size(player_idx);
+    let player_hand = &gs.player_hands[player_idx];
+
+    if player_hand.must_have.len() != hand_size {
+        return false;
+    }
+
+    let holder = Holder::Player(player_idx);
+    let premises: Vec<Fact> = player_hand.must_have.iter()
+        .map(|card| Fact::new(holder, card, true))
+        .collect();
+
+    let deductions: Vec<Deduction> = (&(&gs.deck.get_all_cards() - &player_hand.must_have) - &player_hand.must_not_have)
+        .into_iter()
+        .map(|card| Deduction::new(Fact::new(holder, card, false), Rule::HandIsFull)
+            .because(premises.clone()))
+        .collect();
+
+    add_deductions(gs, deductions)
+}
+
+/// If the cards a player could still have exactly fill the rest of
+/// their hand, they must have all of them.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_fills_rest_of_hand(gs: &mut GameState, player_idx: usize) -> bool {
+    let hand_size = gs.get_hand_size(player_idx);
+    let player_hand = &gs.player_hands[player_idx];
+
+    let undecided_cards: CardSet = 
+        &(&(&gs.deck.get_all_cards() - &gs.public_cards) - &player_hand.must_have) - &player_hand.must_not_have;
+
+    if undecided_cards.is_empty() || player_hand.must_have.len() + undecided_cards.len() != hand_size {
+        return false;
+    }
+
+    let holder = Holder::Player(player_idx);
+    let premises: Vec<Fact> = get_known_facts(gs, holder)
+        .into_iter()
+        .filter(|fact| !gs.public_cards.contains(&fact.card))
+        .collect();
+
+    let deductions: Vec<Deduction> = undecided_cards.into_iter()
+        .map(|card| Deduction::new(Fact::new(holder, card, true), Rule::FillsRestOfHand)
+            .because(premises.clone()))
+        .collect();
+
+    add_deductions(gs, deductions)
+}
+
+/// Adds each deduction to the game state.
+/// 
+/// Returns whether anything was learned.
+fn add_deductions(gs: &mut GameState, deductions: Vec<Deduction>) -> bool {
+    let mut changed = false;
+
+    for deduction in deductions {
+        if gs.add_deduction(deduction) {
+            changed = true;
+        }
+    }
+
+    changed
+}
+
+/// Everything we know about a holder's hand, as facts
+fn get_known_facts(gs: &GameState, holder: Holder) -> Vec<Fact> {
+    let hand = gs.get_hand(holder);
+
+    hand.must_have.iter()
+        .map(|card| Fact::new(holder, card, true))
+        .chain(hand.must_not_have.iter().map(|card| Fact::new(holder, card, false)))
+        .collect()
+}
+
+/// Why a player must have a card, when it's the only card left they
+/// could have shown for an accusation.
+fn get_only_remaining_option_deduction(gs: &GameState, player_idx: usize, card: &Card) -> Deduction {
+    let holder = Holder::Player(player_idx);
+    let player_hand = &gs.player_hands[player_idx];
+
+    let deduction = Deduction::new(Fact::new(holder, *card, true), Rule::OnlyRemainingOption);
+
+    for (acc_idx, acc) in gs.accusations.iter().enumerate() {
+        let showed_unseen_card = gs.get_responses(acc).iter()
+            .any(|response| response.player_index == player_idx && response.card_shown.is_none());
+
+        if !showed_unseen_card {
+            continue;
+        }
+
+        let accused_cards = acc.accused_cards.clone();
+
+        if !accused_cards.contains(card) {
+            continue;
+        }
+
+        let other_cards: Vec<Card> = accused_cards.into_iter().filter(|accused_card| accused_card != card).collect();
+
+        if other_cards.iter().all(|other_card| player_hand.must_not_have.contains(other_card)) {
+            return deduction
+                .from_accusation(acc_idx)
+                .because(other_cards.into_iter().map(|other_card| Fact::new(holder, other_card, false)).collect());
+        }
+    }
+
+    deduction
+}
+
+/// Narrows down what a player must have at least one of.
+/// 
+/// Cards the player can't have are dropped from each group, and groups that
+/// already contain a must have, or contain another group, are dropped. A group
+/// with one card left is a must have.
+/// 
+/// If the player has as many groups with no cards in common as they have open
+/// slots, each slot is filled by one of those groups, so they can't have any card
+/// outside of them.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_must_have_one_of(gs: &mut GameState, player_idx: usize) -> bool {
+    let hand_size = gs.get_hand_size(player_idx);
+    let mut changed = false;
+
+    let holder = Holder::Player(player_idx);
+    let player_hand = &gs.player_hands[player_idx];
+
+    let mut narrowed: Vec<CardSet> = Vec::new();
+
+    for cards in player_hand.must_have_one_of.iter() {
+        if !cards.is_disjoint(&player_hand.must_have) {
+            // Already satisfied
+            changed = true;
+            continue;
+        }
+
+        let possible_cards: CardSet = cards - &player_hand.must_not_have;
+
+        if possible_cards.is_empty() {
+            // Contradiction, keep it as is so verify_state can report it
+            narrowed.push(*cards);
+            continue;
+        }
+
+        if possible_cards.len() != cards.len() {
+            changed = true;
+        }
+
+        narrowed.push(possible_cards);
+    }
+
+    // Groups with a single card left are must haves
+    let deductions: Vec<Deduction> = narrowed.iter()
+        .filter(|cards| cards.len() == 1 && cards.is_disjoint(&player_hand.must_not_have))
+        .flat_map(|cards| cards.iter())
+        .map(|card| get_only_remaining_option_deduction(gs, player_idx, &card))
+        .collect();
+
+    changed |= add_deductions(gs, deductions);
+
+    let player_hand = &gs.player_hands[player_idx];
+
+    // Drop groups which are satisfied, or contain a smaller group
+    narrowed.sort_by_key(|cards| cards.len());
+
+    let mut kept: Vec<CardSet> = Vec::new();
+
+    for cards in narrowed {
+        if !cards.is_disjoint(&player_hand.must_have) 
+            || kept.iter().any(|smaller| smaller.is_subset(&cards)) {
+            changed = true;
+            continue;
+        }
+
+        kept.push(cards);
+    }
+
+    gs.player_hands[player_idx].must_have_one_of = kept;
+
+    let player_hand = &gs.player_hands[player_idx];
+
+    // Compare against the open slots in their hand
+    let open_slots = hand_size.saturating_sub(player_hand.must_have.len());
+
+    let possible_groups: Vec<&CardSet> = player_hand.must_have_one_of.iter()
+        .filter(|cards| !cards.is_subset(&player_hand.must_not_have))
+        .collect();
+
+    if open_slots == 0 || possible_groups.len() < open_slots {
+        return changed;
+    }
+
+    if let Some(disjoint_groups) = find_disjoint_groups(&possible_groups, open_slots, &mut Vec::new(), 0) {
+        let mut cards_in_groups: CardSet = CardSet::new();
+        for group_idx in disjoint_groups {
+            cards_in_groups |= possible_groups[group_idx];
+        }
+
+        let must_not_haves_to_add: CardSet = 
+            &(&(&gs.deck.get_all_cards() - &player_hand.must_have) - &cards_in_groups) - &player_hand.must_not_have;
+
+        let premises: Vec<Fact> = player_hand.must_have.iter()
+            .map(|card| Fact::new(holder, card, true))
+            .collect();
+
+        let deductions: Vec<Deduction> = must_not_haves_to_add.into_iter()
+            .map(|card| Deduction::new(Fact::new(holder, card, false), Rule::GroupsFillHand)
+                .because(premises.clone()))
+            .collect();
+
+        changed |= add_deductions(gs, deductions);
+    }
+
+    changed
+}
+
+/// Finds the indices of number_wanted groups which have no cards in common.
+fn find_disjoint_groups(groups: &[&CardSet], number_wanted: usize, 
+    chosen: &mut Vec<usize>, start_idx: usize) -> Option<Vec<usize>> {
+
+    if chosen.len() == number_wanted {
+        return Some(chosen.clone());
+    }
+
+    for group_idx in start_idx..groups.len() {
+        if chosen.iter().any(|chosen_idx| !groups[*chosen_idx].is_disjoint(groups[group_idx])) {
+            continue;
+        }
+
+        chosen.push(group_idx);
+
+        if let Some(found) = find_disjoint_groups(groups, number_wanted, chosen, group_idx + 1) {
+            return Some(found);
+        }
+
+        chosen.pop();
+    }
+
+    None
+}
+
+/// Holders which could still take a card that the pigeonhole rule can check
+/// every group of, as there are twice as many groups with each one more
+const MAX_OPEN_HOLDERS: usize = 16;
+
+/// Pigeonhole rule across every hand and the envelope.
+/// 
+/// Cards nobody is known to have yet must fit into the open slots left in
+/// the hands and the envelope. If the cards which can only go to some group
+/// of holders exactly fill that group's open slots, no one in the group can
+/// have any other card.
+/// 
+/// Each category of the envelope counts as its own holder with one slot.
+/// Only holders which could still take a card are grouped, and there can be
+/// at most MAX_OPEN_HOLDERS of them.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_open_slot_counting(gs: &mut GameState) -> Result<bool> {
+    apply_open_slot_counting_to_groups(gs, None)
+}
+
+/// Pigeonhole rule, only for the groups of holders whose open slots or
+/// cards could have changed because of the facts.
+/// 
+/// Returns whether anything was learned.
+pub(crate) fn apply_open_slot_counting_after(gs: &mut GameState, facts: &[Fact]) -> Result<bool> {
+    apply_open_slot_counting_to_groups(gs, Some(facts))
+}
+
+fn apply_open_slot_counting_to_groups(gs: &mut GameState, facts: Option<&[Fact]>) -> Result<bool> {
+    let number_of_players = gs.player_hands.len();
+    let number_of_holders = number_of_players + gs.deck.get_number_of_categories();
+
+    let mut placed_cards: CardSet = gs.public_cards;
+    for hand in gs.player_hands.iter().chain(std::iter::once(&gs.envelope)) {
+        placed_cards |= &hand.must_have;
+    }
+
+    let unplaced_cards: Vec<Card> = (&gs.deck.get_all_cards() - &placed_cards).into_iter().collect();
+
+    // Holders are the players, then one per envelope category
+    let mut open_slots: Vec<usize> = gs.player_hands.iter()
+        .enumerate()
+        .map(|(player_idx, hand)| gs.get_hand_size(player_idx).saturating_sub(hand.must_have.len()))
+        .collect();
+
+    for category_index in 0..gs.deck.get_number_of_categories() {
+        let category_cards = gs.deck.get_all_cards_in_category(category_index);
+        open_slots.push(if category_cards.is_disjoint(&gs.envelope.must_have) { 1 } else { 0 });
+    }
+
+    let get_possible_holders = |card: &Card| -> Vec<usize> {
+        let mut holders: Vec<usize> = (0..number_of_players)
+            .filter(|player_idx| !gs.player_hands[*player_idx].must_not_have.contains(card))
+            .collect();
+
+        if !gs.envelope.must_not_have.contains(card) {
+            holders.push(number_of_players + gs.deck.get_category_index(card));
+        }
+
+        holders
+    };
+
+    let possible_holders: Vec<Vec<usize>> = unplaced_cards.iter().map(get_possible_holders).collect();
+
+    // Only holders which could still take a card get a bit
+    let mut bit_of_holder: Vec<Option<usize>> = vec![None; number_of_holders];
+    let mut holder_of_bit: Vec<usize> = Vec::new();
+
+    for holder_idx in possible_holders.iter().flatten() {
+        if bit_of_holder[*holder_idx].is_none() {
+            bit_of_holder[*holder_idx] = Some(holder_of_bit.len());
+            holder_of_bit.push(*holder_idx);
+        }
+    }
+
+    if holder_of_bit.len() > MAX_OPEN_HOLDERS {
+        bail!(ErrorKind::TooManyOpenHolders(holder_of_bit.len(), MAX_OPEN_HOLDERS));
+    }
+
+    let get_bits = |holders: &[usize]| -> Option<u64> {
+        holders.iter().try_fold(0u64, |bits, holder_idx| Some(bits | 1 << bit_of_holder[*holder_idx]?))
+    };
+
+    let possible_holder_bits: Vec<u64> = possible_holders.iter()
+        .map(|holders| get_bits(holders).unwrap())
+        .collect();
+
+    let all_bits: u64 = possible_holder_bits.iter().fold(0, |bits, holders| bits | holders);
+
+    // Every group, or only those the facts change. A holder getting a card has
+    // one less slot, and a card ruled out of a hand now counts toward groups
+    // with every holder it has left.
+    let mut groups: Vec<u64> = match facts {
+        None => get_holder_groups(all_bits, 0),
+        Some(facts) => {
+            let mut groups: Vec<u64> = Vec::new();
+
+            for fact in facts {
+                let holders = if fact.has {
+                    match fact.holder {
+                        Holder::Player(player_idx) => vec![player_idx],
+                        Holder::Envelope => vec![number_of_players + gs.deck.get_category_index(&fact.card)],
+                    }
+                } else if placed_cards.contains(&fact.card) {
+                    continue;
+                } else {
+                    get_possible_holders(&fact.card)
+                };
+
+                // A holder which can't take any more cards changes no group
+                if let Some(including) = get_bits(&holders).filter(|including| *including != 0) {
+                    groups.extend(get_holder_groups(all_bits, including));
+                }
+            }
+
+            groups
+        },
+    };
+
+    groups.sort();
+    groups.dedup();
+
+    let is_in_group = |group: u64, holder_idx: usize| {
+        bit_of_holder[holder_idx].is_some_and(|bit| group & (1 << bit) != 0)
+    };
+
+    let holder_of_idx = |holder_idx: usize| {
+        if holder_idx < number_of_players { Holder::Player(holder_idx) } else { Holder::Envelope }
+    };
+
+    let mut changed = false;
+
+    for group in groups {
+        let group_open_slots: usize = (0..holder_of_bit.len())
+            .filter(|bit| group & (1 << bit) != 0)
+            .map(|bit| open_slots[holder_of_bit[bit]])
+            .sum();
+
+        let number_only_in_group = possible_holder_bits.iter()
+            .filter(|holders| **holders != 0 && **holders & !group == 0)
+            .count();
+
+        if number_only_in_group != group_open_slots {
+            continue;
+        }
+
+        // The group is full, so the other cards go elsewhere
+        let mut premises: Vec<Fact> = Vec::new();
+        let mut deductions: Vec<Deduction> = Vec::new();
+
+        for (card, holders) in unplaced_cards.iter().zip(possible_holder_bits.iter()) {
+            if *holders != 0 && holders & !group == 0 {
+                // Can only go to the group, because every other holder can't have it
+                let category_idx = number_of_players + gs.deck.get_category_index(card);
+
+                premises.extend((0..number_of_holders)
+                    .filter(|holder_idx| !is_in_group(group, *holder_idx)
+                        && (*holder_idx < number_of_players || *holder_idx == category_idx))
+                    .map(|holder_idx| Fact::new(holder_of_idx(holder_idx), *card, false)));
+            }
+        }
+
+        for (card, holders) in unplaced_cards.iter().zip(possible_holder_bits.iter()) {
+            if holders & !group == 0 || holders & group == 0 {
+                continue;
+            }
+
+            for (bit, holder_idx) in holder_of_bit.iter().enumerate() {
+                if holders & group & (1 << bit) == 0 {
+                    continue;
+                }
+
+                deductions.push(Deduction::new(Fact::new(holder_of_idx(*holder_idx), *card, false), Rule::OpenSlotsFilled)
+                    .because(premises.clone()));
+            }
+        }
+
+        changed |= add_deductions(gs, deductions);
+    }
+
+    Ok(changed)
+}
+
+/// Every group of the holders in available which includes all of including.
+fn get_holder_groups(available: u64, including: u64) -> Vec<u64> {
+    let others: Vec<u32> = (0..u64::BITS)
+        .filter(|bit| available & !including & (1 << bit) != 0)
+        .collect();
+
+    let mut groups: Vec<u64> = Vec::new();
+    add_holder_groups(&others, including, &mut groups);
+
+    groups
+}
+
+fn add_holder_groups(others: &[u32], group: u64, groups: &mut Vec<u64>) {
+    if group != 0 {
+        groups.push(group);
+    }
+
+    for (position, bit) in others.iter().enumerate() {
+        add_holder_groups(&others[position + 1..], group | (1 << bit), groups);
+    }
+}
+
+/// Determines what cards must and could be
+pub fn get_potentially_winning_cards(gs: &GameState) -> CardSet{
+    let mut potentially_winning_cards: CardSet = gs.deck.get_all_cards(); 
+
+    // Remove Cards players must have
+    for hand in gs.player_hands.iter() {
+        potentially_winning_cards = &potentially_winning_cards - &hand.must_have;
+    }
+
+    potentially_winning_cards = &potentially_winning_cards - &gs.public_cards;
+
+    potentially_winning_cards = &potentially_winning_cards - &gs.envelope.must_not_have;
+
+    potentially_winning_cards
+}
+
+pub fn get_guaranteed_winning_cards(gs: &GameState) -> CardSet {
+    // Get all the cards
+    let mut common_do_not_haves: CardSet = gs.deck.get_all_cards();
+
+    // Keep cards that everyone must not have.
+    for hand in gs.player_hands.iter() {
+        common_do_not_haves &= &hand.must_not_have;
+    }
+
+    // Remove elements which are in public cards
+    common_do_not_haves = &common_do_not_haves - &gs.public_cards;
+
+    // Add cards we've proven are in the envelope
+    common_do_not_haves |= &gs.envelope.must_have;
+
+    common_do_not_haves
+}
+
+/// Gets index of players who are between start and end, wrapping around if neccessary.
+/// 
+/// Does not include the end_idx or start_idx
+/// 
+/// If end_idx is None, then it gets all players except start_idx
+/// 
+/// start_idx must be < size, same with end_idx.
+pub fn get_responding_players(start_idx: usize,end_idx: Option<usize>, size: usize) -> Vec<usize> {
+    
+    let mut responding_players: Vec<usize> = Vec::new();
+    
+    for i in 0..(size-1) {
+        let current_player_idx = (i + start_idx + 1) % size;
+
+        // If at end, stop
+        if end_idx.is_some() && current_player_idx == end_idx.unwrap() {
+            return responding_players;
+        }
+
+        responding_players.push(current_player_idx);
+    }
+
+    responding_players
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::accusation::{Accusation, FinalAccusation, Response};
+    use crate::deck::{Deck, tests::card};
+    use crate::player_hand::PlayerHand;
+    use crate::deduction::Fact;
+    use crate::observation::Observation;
+    use crate::rules::{GameRules, ResponseRule};
+
+    use super::*;
+
+    #[test]
+    fn test_propogate_state_not_responding_adds_does_not_haves() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+            PlayerHand::new("p4".to_owned()),
+        ];
+
+
+        // p1 must have Green
+        player_hands
+            .get_mut(0)
+            .unwrap()
+            .must_have
+            .insert(card("Green"));
+        
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        // Everyone else, not p1, must not have green
+        propagate_state(&mut gs).unwrap();
+
+
+        // Check everyone else must not have green
+        for i in [1,2,3] {
+            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 1);
+            assert_eq!(gs.player_hands.get(i).unwrap().must_have.len(), 0);
+
+            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&card("Green")));
+
+        }
+
+        // p1 should not change, must have green
+        assert_eq!(gs.player_hands.first().unwrap().must_not_have.len(), 0);
+        assert_eq!(gs.player_hands.first().unwrap().must_have.len(), 1);
+
+        assert!(gs.player_hands.first().unwrap().must_have.contains(&card("Green")));
+      
+        
+    }
+
+    #[test]
+    fn test_propogate_state_if_player_must_have_other_players_must_not_have() {
+
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+            PlayerHand::new("p4".to_owned()),
+        ];
+
+        let accusations = vec![
+            Accusation { 
+                accuser_player_index: 0, 
+                accused_cards: vec![card("Study"), card("Knife"), card("Peacock")],
+                responding_player_index: Some(3), 
+                card_shown: None,
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            }
+        ];
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations,
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        for i in [0, 3] {
+            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 0);
+            assert_eq!(gs.player_hands.get(i).unwrap().must_have.len(), 0);
+        }
+
+
+        for i in 1..=2 {
+            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 3);
+            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&card("Study")));
+            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&card("Peacock")));
+            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&card("Knife")));
+        }
+        
+    }
+
+    #[test]
+    fn test_propogate_state_unknown_accusation_with_hand() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+            PlayerHand::new("p4".to_owned()),
+        ];
+
+
+        // p1 does not have Green or Pistol
+        let p1 = player_hands
+            .get_mut(0)
+            .unwrap();
+
+        p1.must_not_have
+            .insert(card("Green"));
+
+        p1.must_not_have
+        .insert(card("Pistol"));
+        
+
+        let accusations = vec![
+            Accusation { 
+                accuser_player_index: 3, 
+                accused_cards: vec![card("Study"), card("Pistol"), card("Green")],
+                responding_player_index: Some(0), 
+                card_shown: None,
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            }
+        ];
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations,
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+
+        // Check that p1 must have Study
+        assert_eq!(gs.player_hands.first().unwrap().must_not_have.len(), 2);
+        assert_eq!(gs.player_hands.first().unwrap().must_have.len(), 1);
+
+        assert!(gs.player_hands.first().unwrap().must_have.contains(&card("Study")));
+
+
+        // Check that others must not have Study (since p1 has it)
+        for i in [1,2,3] {
+            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 1);
+            assert_eq!(gs.player_hands.get(i).unwrap().must_have.len(), 0);
+
+            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&card("Study")));
+        }
+    }
+    
+    #[test]
+    fn test_propogate_state_last_card_of_category_is_in_envelope() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
+
+        // Players hold every room but the Billiard Room between them
+        let held_rooms = [
+            card("Kitchen"), card("Ballroom"), card("Conservatory"), card("Dining Room"),
+            card("Lounge"), card("Hall"), card("Study"), card("Library"),
+        ];
+
+        for (idx, room) in held_rooms.into_iter().enumerate() {
+            player_hands[idx % 3].must_have.insert(room);
+        }
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        assert!(gs.envelope.must_have.contains(&card("Billiard")));
+
+        for player_hand in gs.player_hands.iter() {
+            assert!(player_hand.must_not_have.contains(&card("Billiard")));
+        }
+
+        let guaranteed_winning_cards = get_guaranteed_winning_cards(&gs);
+        assert_eq!(guaranteed_winning_cards.len(), 1);
+        assert!(guaranteed_winning_cards.contains(&card("Billiard")));
+    }
+
+    #[test]
+    fn test_propogate_state_envelope_card_rules_out_rest_of_category() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
+
+        // p1 has the Study and Peacock but not the Rope
+        player_hands[0].must_have.insert(card("Study"));
+        player_hands[0].must_have.insert(card("Peacock"));
+        player_hands[0].must_not_have.insert(card("Rope"));
+
+        // Nobody could show the Rope
+        let accusations = vec![
+            Accusation { 
+                accuser_player_index: 0, 
+                accused_cards: vec![card("Study"), card("Rope"), card("Peacock")],
+                responding_player_index: None, 
+                card_shown: None,
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            }
+        ];
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations,
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        assert!(gs.envelope.must_have.contains(&card("Rope")));
+
+        // Every other weapon is in someone's hand
+        for weapon in gs.deck.get_all_cards_in_category(1).iter().filter(|weapon| *weapon != card("Rope")) {
+            assert!(gs.envelope.must_not_have.contains(&weapon));
+        }
+
+        let potentially_winning_cards = get_potentially_winning_cards(&gs);
+        assert!(potentially_winning_cards.contains(&card("Rope")));
+        assert!(!potentially_winning_cards.contains(&card("Knife")));
+        assert!(!potentially_winning_cards.contains(&card("Study")));
+    }
+
+    #[test]
+    fn test_propogate_state_undecided_cards_fill_hand() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
+
+        let mut all_cards: Vec<Card> = Deck::classic().get_all_cards().into_iter().collect();
+        all_cards.sort();
+
+        // p2 has two cards, and could only have four more of the rest
+        let p2 = player_hands.get_mut(1).unwrap();
+        p2.must_have.extend(all_cards[0..2].iter().cloned());
+        p2.must_not_have.extend(all_cards[6..].iter().cloned());
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        let p2 = gs.player_hands.get(1).unwrap();
+        assert_eq!(p2.must_have.len(), 6);
+        for card in all_cards[0..6].iter() {
+            assert!(p2.must_have.contains(card));
+        }
+
+        // Nobody else can have p2's cards
+        for i in [0, 2] {
+            for card in all_cards[0..6].iter() {
+                assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(card));
+            }
+        }
+    }
+
+    #[test]
+    fn test_propogate_state_uses_each_hand_size() {
+
+        // Every card is dealt, p1 and p2 get five cards and p3 and p4 get four
+        let mut player_hands: Vec<PlayerHand> = Deck::classic().get_dealt_hand_sizes(4, 3, Default::default())
+            .into_iter()
+            .enumerate()
+            .map(|(idx, hand_size)| PlayerHand::new(format!("p{}", idx + 1)).with_hand_size(hand_size))
+            .collect();
+
+        let mut all_cards: Vec<Card> = Deck::classic().get_all_cards().into_iter().collect();
+        all_cards.sort();
+
+        let p3_cards: CardSet = [0, 1, 9, 15].into_iter().map(|idx| all_cards[idx]).collect();
+        let p2_cards: CardSet = [2, 3, 10, 11, 16].into_iter().map(|idx| all_cards[idx]).collect();
+
+        // Our four cards fill our hand
+        player_hands[2].must_have = p3_cards;
+
+        // p2 could only have five cards, which must be theirs
+        player_hands[1].must_not_have = &Deck::classic().get_all_cards() - &p2_cards;
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(2),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        assert_eq!(gs.player_hands[2].must_not_have.len(), 17);
+
+        assert_eq!(gs.player_hands[1].must_have, p2_cards);
+
+        gs.verify_state().unwrap();
+    }
+
+    #[test]
+    fn test_propogate_state_cards_fit_in_open_slots() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
+
+        let unplaced_cards: CardSet = [
+            card("Kitchen"),
+            card("Study"),
+            card("Rope"),
+            card("Knife"),
+            card("Plum"),
+        ].into_iter().collect();
+
+        let mut other_cards: Vec<Card> = (&Deck::classic().get_all_cards() - &unplaced_cards)
+            .into_iter()
+            .collect();
+        other_cards.sort();
+
+        // p1 has a complete hand, p2 and p3 each have one card left to find
+        player_hands[0].must_have.extend(other_cards[0..6].iter().cloned());
+        player_hands[1].must_have.extend(other_cards[6..11].iter().cloned());
+        player_hands[2].must_have.extend(other_cards[11..16].iter().cloned());
+
+        // p3 can't have either room, so p2 and the envelope split them
+        player_hands[2].must_not_have.insert(card("Kitchen"));
+        player_hands[2].must_not_have.insert(card("Study"));
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        // p2's last slot goes to a room
+        let p2 = gs.player_hands.get(1).unwrap();
+        assert!(p2.must_not_have.contains(&card("Rope")));
+        assert!(p2.must_not_have.contains(&card("Knife")));
+        assert!(p2.must_not_have.contains(&card("Plum")));
+
+        // The only suspect left is in the envelope
+        assert!(gs.envelope.must_have.contains(&card("Plum")));
+        assert_eq!(gs.envelope.must_have.len(), 1);
+    }
+
+    #[test]
+    fn test_propogate_state_remembers_unknown_shown_cards() {
+
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+            PlayerHand::new("p4".to_owned()),
+        ];
+
+        let accusations = vec![
+            // p2 shows p4 a card we don't see
+            Accusation { 
+                accuser_player_index: 3, 
+                accused_cards: vec![card("Study"), card("Pistol"), card("Green")],
+                responding_player_index: Some(1), 
+                card_shown: None,
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            },
+            // p2 can't show Green
+            Accusation { 
+                accuser_player_index: 0, 
+                accused_cards: vec![card("Hall"), card("Rope"), card("Green")],
+                responding_player_index: Some(2), 
+                card_shown: None,
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            },
+        ];
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations,
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
 
-    // -> Must have
-    // If a player shows a card, they must have that card
-    for acc in gs.accusations.iter() {
-        if acc.card_shown.is_none() {
-            continue;
-        }
+        propagate_state(&mut gs).unwrap();
 
-        gs.player_hands
-            .get_mut(acc.responding_player_index
-                .expect("valid states that have a card shown, also have a responding_player_index ")
-            )
-            .expect("valid states have valid responding_player_index")
-            .must_have
-            .insert(acc.card_shown.clone()
-                .expect("Checked that card_shown is Ok() above"));
+        let expected_cards: CardSet = 
+            [card("Study"), card("Pistol")].into_iter().collect();
+
+        let p2 = gs.player_hands.get(1).unwrap();
+        assert_eq!(p2.must_have_one_of.len(), 1);
+        assert_eq!(p2.must_have_one_of[0], expected_cards);
+        assert_eq!(p2.must_have.len(), 0);
     }
 
-    // -> Does not haves
-    // Propogate Does not Haves, if someone could not respond
-    for acc in gs.accusations.iter_mut() {
-        let players_who_did_not_have_cards 
-            = get_responding_players(
-                acc.accuser_player_index, 
-                acc.responding_player_index, 
-                gs.player_hands.len()
-            );
+    #[test]
+    fn test_propogate_state_drops_subsumed_must_have_one_of() {
+
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
+
+        let smaller: CardSet = 
+            [card("Study"), card("Pistol")].into_iter().collect();
 
-        for player_who_did_not_have_card_index in players_who_did_not_have_cards {
-            
-            let must_not_have_set = 
-                &mut gs.player_hands
-                    .get_mut(player_who_did_not_have_card_index)
-                    .expect("valid player indicies from players_who_did_not_have_cards")
-                    .must_not_have;
+        let larger: CardSet = 
+            [card("Study"), card("Pistol"), card("Plum")]
+                .into_iter().collect();
 
+        player_hands[1].must_have_one_of.push(larger);
+        player_hands[1].must_have_one_of.push(smaller);
 
-                must_not_have_set.insert(Card::RoomCard(acc.room.clone()));
-                must_not_have_set.insert(Card::WeaponCard(acc.weapon.clone()));
-                must_not_have_set.insert(Card::SuspectCard(acc.suspect.clone()));
-        }
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
 
+        let p2 = gs.player_hands.get(1).unwrap();
+        assert_eq!(p2.must_have_one_of.len(), 1);
+        assert_eq!(p2.must_have_one_of[0], smaller);
     }
 
+    #[test]
+    fn test_propogate_state_disjoint_must_have_one_of_fill_hand() {
 
-    // Since the next two blocks might impact each other,
-    // we keep running them until no more changes occur.
-    let mut changed = true;
-    while changed {
-        changed = false;
+        let mut player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
 
-        // Must Haves -> Does not haves
-        // If someone must have a card, everyone else must not have that card
-        let mut all_must_haves: HashSet<Card> = HashSet::new();
+        // p2 has 4 of their 6 cards, and one card from each of two groups
+        let known_cards = [
+            card("Kitchen"), card("Hall"),
+            card("Knife"), card("White"),
+        ];
+        player_hands[1].must_have.extend(known_cards.iter().cloned());
 
-        for player_hand in gs.player_hands.iter() {
-            all_must_haves.extend(player_hand.must_have.clone().into_iter());
-        }
+        let first_group: CardSet = 
+            [card("Study"), card("Pistol")].into_iter().collect();
+        let second_group: CardSet = 
+            [card("Lounge"), card("Plum")].into_iter().collect();
 
-        for player_hand in gs.player_hands.iter_mut() {
-            for card_does_not_have in all_must_haves.difference(&player_hand.must_have) {
-                if player_hand.must_not_have.insert(card_does_not_have.clone()) {
-                    changed = true;
-                }
-            }
+        player_hands[1].must_have_one_of.push(first_group);
+        player_hands[1].must_have_one_of.push(second_group);
+
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
+
+        propagate_state(&mut gs).unwrap();
+
+        let p2 = gs.player_hands.get(1).unwrap();
+
+        let possible_cards: CardSet = &(&CardSet::from(known_cards) | &first_group) | &second_group;
+
+        for card in gs.deck.get_all_cards() {
+            assert_eq!(p2.must_not_have.contains(&card), !possible_cards.contains(&card), "{:?}", card);
         }
 
-        // Does Not haves -> Must Have
-        // If Unknown Accusation, check if the 2 cards they don't have, therefore responding player
-        // must have the third card.
-        
-        for acc in gs.accusations.iter() {
-            if acc.responding_player_index.is_none() {
-                // No One Responded to accusation.
-                continue;
-            }
+        assert_eq!(p2.must_have_one_of.len(), 2);
+    }
 
+    #[test]
+    fn test_propogate_state_records_deductions() {
 
-            // Already know the card
-            if acc.card_shown.is_some() {
-                continue;
-            }
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
 
-            let potentially_shown_cards: HashSet<Card> = 
-                vec![
-                    Card::SuspectCard(acc.suspect.clone()),
-                    Card::RoomCard(acc.room.clone()),
-                    Card::WeaponCard(acc.weapon.clone())
-                ].into_iter().collect();
+        let accusations = vec![
+            // p2 could not respond, p3 showed the Knife
+            Accusation {
+                accuser_player_index: 0,
+                accused_cards: vec![card("Conservatory"), card("Knife"), card("Green")],
+                responding_player_index: Some(2),
+                card_shown: Some(card("Knife")),
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            },
+            // Nobody could respond
+            Accusation {
+                accuser_player_index: 1,
+                accused_cards: vec![card("Hall"), card("Pipe"), card("Green")],
+                responding_player_index: None,
+                card_shown: None,
+                responses: Vec::new(),
+                asked_players: None,
+                possible_responders: Vec::new(),
+            },
+        ];
 
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations,
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
 
-            let responding_player_hand = gs.player_hands.get_mut(acc.responding_player_index.unwrap()).unwrap();
+        propagate_state(&mut gs).unwrap();
 
-            if !potentially_shown_cards.is_disjoint(&responding_player_hand.must_have) {
-                // At least one of our potential cards we already know they have, no new info
-                continue;
+        let green_in_envelope = Fact::new(Holder::Envelope, card("Green"), true);
+        let deduction = gs.get_deduction(&green_in_envelope).unwrap();
+
+        assert_eq!(deduction.rule, Rule::OnlyPlaceLeft);
+        assert_eq!(deduction.premises.len(), 3);
+
+        let knife = gs.get_deduction(&Fact::new(Holder::Player(2), card("Knife"), true)).unwrap();
+        assert_eq!(knife.rule, Rule::ShowedCard);
+        assert_eq!(knife.accusation_index, Some(0));
+
+        // Every premise is explained before the fact it leads to
+        let chain = crate::deduction::get_reasoning_chain(&gs, &green_in_envelope);
+        assert_eq!(chain.last(), Some(&green_in_envelope));
+
+        for (step_idx, fact) in chain.iter().enumerate() {
+            for premise in gs.get_deduction(fact).unwrap().premises.iter() {
+                assert!(chain[..step_idx].contains(premise));
             }
+        }
 
-            let potentially_shown_cards: HashSet<&Card> = 
-                potentially_shown_cards.difference(&responding_player_hand.must_not_have).collect();
+        // Every fact learned has a reason, and only one
+        for holder in gs.get_holders() {
+            let hand = gs.get_hand(holder);
 
-            if potentially_shown_cards.len() == 1 {
-                // Only one option for them to show, they must have this card.
-                responding_player_hand.must_have.insert(potentially_shown_cards.into_iter().next().unwrap().clone());
-                changed = true;
+            for (cards, has) in [(&hand.must_have, true), (&hand.must_not_have, false)] {
+                for card in cards {
+                    let fact = Fact::new(holder, card, has);
+                    assert_eq!(gs.deductions.iter().filter(|deduction| deduction.fact == fact).count(), 1);
+                }
             }
         }
+    }
 
+    #[test]
+    fn test_propogate_state_everyone_shows() {
 
-        let number_of_expected_cards_per_hand = gs.get_number_of_expected_cards_per_hand();
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+            PlayerHand::new("p4".to_owned()),
+        ];
 
-        // Must Have -> Does not have
-        // If a player's must have is complete, then finish completing their
-        // must not have with every other card.
-        for player_hand in gs.player_hands.iter_mut() {
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: vec![
+                // p3 showed p1 the Knife, and p4 showed another card
+                Accusation {
+                    accuser_player_index: 0,
+                    accused_cards: vec![card("Hall"), card("Knife"), card("Plum")],
+                    responding_player_index: None,
+                    card_shown: None,
+                    responses: vec![
+                        Response { player_index: 2, card_shown: Some(card("Knife")) },
+                        Response { player_index: 3, card_shown: None },
+                    ],
+                    asked_players: None,
+                    possible_responders: Vec::new(),
+                },
+            ],
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: GameRules { response_rule: ResponseRule::EveryoneShows, ..Default::default() },
+            reveal: None,
+        };
 
-            let expected_number_of_does_not_haves_for_complete_hand = 
-                Card::get_total_cards() - number_of_expected_cards_per_hand;
+        propagate_state(&mut gs).unwrap();
 
-            if player_hand.must_not_have.len() == expected_number_of_does_not_haves_for_complete_hand {
-                continue;
-            }
+        assert!(gs.player_hands[2].must_have.contains(&card("Knife")));
 
-            if player_hand.must_have.len() == number_of_expected_cards_per_hand {
-                let must_not_haves_to_add = &Card::get_all_cards() - &player_hand.must_have;
-                player_hand.must_not_have.extend(must_not_haves_to_add);
-                changed = true;
-            }
-        } 
+        // p2 was asked before them, yet had none of the cards
+        for card in [card("Hall"), card("Knife"), card("Plum")] {
+            assert!(gs.player_hands[1].must_not_have.contains(&card));
+        }
+
+        // p3 has the Knife, so p4 showed the Hall or Plum
+        assert_eq!(gs.player_hands[3].must_have_one_of, vec![
+            [card("Hall"), card("Plum")].into_iter().collect::<CardSet>()]);
+        assert!(gs.player_hands[2].must_have_one_of.is_empty());
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_propogate_state_wrong_final_accusation() {
 
-/// Determines what cards must and could be
-pub fn get_potentially_winning_cards(gs: &GameState) -> HashSet<Card>{
-    let mut potentially_winning_cards: HashSet<Card> = Card::get_all_cards(); 
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
 
-    // Remove Cards players must have
-    for hand in gs.player_hands.iter() {
-        potentially_winning_cards = &potentially_winning_cards - &hand.must_have;
-    }
+        let mut envelope = PlayerHand::new_envelope();
+        envelope.must_have.extend([card("Kitchen"), card("Rope")]);
 
-    potentially_winning_cards = &potentially_winning_cards - &gs.public_cards;
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope,
+            accusations: vec![
+                // p2 is out, yet still can't respond
+                Accusation {
+                    accuser_player_index: 0,
+                    accused_cards: vec![card("Hall"), card("Knife"), card("Plum")],
+                    responding_player_index: Some(2),
+                    card_shown: None,
+                    responses: Vec::new(),
+                    asked_players: None,
+                    possible_responders: Vec::new(),
+                },
+            ],
+            final_accusations: vec![
+                FinalAccusation {
+                    accuser_player_index: 1,
+                    accused_cards: vec![card("Kitchen"), card("Rope"), card("Green")],
+                },
+            ],
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
 
-    potentially_winning_cards
-}
+        propagate_state(&mut gs).unwrap();
 
-pub fn get_guaranteed_winning_cards(gs: &GameState) -> HashSet<Card> {
-    // Get all the cards
-    let mut common_do_not_haves: HashSet<Card> = Card::get_all_cards();
+        // The envelope has the Kitchen and Rope, so Green is who was wrong
+        assert!(gs.envelope.must_not_have.contains(&card("Green")));
+        assert_eq!(gs.get_deduction(&Fact::new(Holder::Envelope, card("Green"), false)).unwrap().rule, 
+            Rule::WrongFinalAccusation);
 
-    // Keep cards that everyone must not have.
-    for hand in gs.player_hands.iter() {
-        common_do_not_haves.retain(|card| hand.must_not_have.contains(card));
+        assert!(gs.player_hands[1].must_not_have.contains(&card("Hall")));
+        assert_eq!(gs.get_eliminated_players(), vec![1]);
     }
 
-    // Remove elements which are in public cards
-    common_do_not_haves = &common_do_not_haves - &gs.public_cards;
+    #[test]
+    fn test_propogate_state_wrong_final_accusation_waits_for_envelope() {
 
-    common_do_not_haves
-}
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
 
-/// Gets index of players who are between start and end, wrapping around if neccessary.
-/// 
-/// Does not include the end_idx or start_idx
-/// 
-/// If end_idx is None, then it gets all players except start_idx
-/// 
-/// start_idx must be < size, same with end_idx.
-pub fn get_responding_players(start_idx: usize,end_idx: Option<usize>, size: usize) -> Vec<usize> {
-    
-    let mut responding_players: Vec<usize> = Vec::new();
-    
-    for i in 0..(size-1) {
-        let current_player_idx = (i + start_idx + 1) % size;
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: Vec::new(),
+            final_accusations: vec![
+                FinalAccusation {
+                    accuser_player_index: 1,
+                    accused_cards: vec![card("Kitchen"), card("Rope"), card("Green")],
+                },
+            ],
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
 
-        // If at end, stop
-        if end_idx.is_some() && current_player_idx == end_idx.unwrap() {
-            return responding_players;
+        propagate_state(&mut gs).unwrap();
+
+        // Any of the three could be wrong
+        assert!(gs.envelope.must_have.is_empty());
+        assert!(gs.envelope.must_not_have.is_empty());
+
+        // Nobody has the Kitchen or Rope, so Green was wrong
+        for player_index in 0..3 {
+            gs.observations.push(Observation { player_index, card: card("Kitchen"), has: false });
+            gs.observations.push(Observation { player_index, card: card("Rope"), has: false });
         }
 
-        responding_players.push(current_player_idx);
+        propagate_state(&mut gs).unwrap();
+
+        assert!(gs.envelope.must_have.contains(&card("Kitchen")));
+        assert!(gs.envelope.must_have.contains(&card("Rope")));
+        assert!(gs.envelope.must_not_have.contains(&card("Green")));
+        assert_eq!(gs.get_deduction(&Fact::new(Holder::Envelope, card("Green"), false)).unwrap().rule,
+            Rule::WrongFinalAccusation);
     }
 
-    responding_players
-}
+    #[test]
+    fn test_propogate_state_observations() {
+
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
+        ];
 
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: vec![
+                // p3 showed p2 one of Hall, Knife or Plum
+                Accusation {
+                    accuser_player_index: 1,
+                    accused_cards: vec![card("Hall"), card("Knife"), card("Plum")],
+                    responding_player_index: Some(2),
+                    card_shown: None,
+                    responses: Vec::new(),
+                    asked_players: None,
+                    possible_responders: Vec::new(),
+                },
+            ],
+            final_accusations: Vec::new(),
+            observations: vec![
+                // p3 dropped the Hall face up, and was seen without the Knife
+                Observation { player_index: 2, card: card("Hall"), has: true },
+                Observation { player_index: 2, card: card("Knife"), has: false },
+            ],
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
 
-#[cfg(test)]
-mod tests {
-    use std::{collections::HashSet, vec};
+        propagate_state(&mut gs).unwrap();
 
-    use crate::accusation::Accusation;
-    use crate::player_hand::PlayerHand;
+        assert!(gs.player_hands[2].must_have.contains(&card("Hall")));
+        assert!(gs.player_hands[1].must_not_have.contains(&card("Hall")));
+        assert_eq!(gs.get_deduction(&Fact::new(Holder::Player(2), card("Hall"), true)).unwrap().rule,
+            Rule::Observed);
 
-    use super::*;
+        // Showing the Hall explains the accusation, so nothing is learned about Plum
+        assert!(gs.player_hands[2].must_have_one_of.is_empty());
+        assert!(!gs.player_hands[2].must_have.contains(&card("Plum")));
+    }
 
     #[test]
-    fn test_propogate_state_not_responding_adds_does_not_haves() {
+    fn test_propogate_state_observed_hand() {
 
         let mut player_hands = vec![
             PlayerHand::new("p1".to_owned()),
             PlayerHand::new("p2".to_owned()),
             PlayerHand::new("p3".to_owned()),
-            PlayerHand::new("p4".to_owned()),
         ];
 
+        player_hands[0].must_have.extend([card("Study"), card("Library"), card("Wrench"),
+            card("Pipe"), card("Scarlet"), card("Mustard")]);
 
-        // p1 must have Green
-        player_hands
-            .get_mut(0)
-            .unwrap()
-            .must_have
-            .insert(Card::SuspectCard(Suspect::Green));
-        
+        let hand = [card("Hall"), card("Lounge"), card("Knife"), card("Rope"), card("Plum"), card("Green")];
 
         let mut gs = GameState {
-            public_cards: HashSet::new(),
-            player_hands: player_hands,
-            self_index: 0,
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
             accusations: Vec::new(),
+            final_accusations: Vec::new(),
+            // p2 left the game, showing their hand
+            observations: hand.iter()
+                .map(|card| Observation { player_index: 1, card: *card, has: true })
+                .collect(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
         };
 
-        // Everyone else, not p1, must not have green
         propagate_state(&mut gs).unwrap();
+        gs.verify_state().unwrap();
 
+        assert_eq!(gs.player_hands[1].must_have, hand.into_iter().collect::<CardSet>());
 
-        // Check everyone else must not have green
-        for i in vec![1,2,3] {
-            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 1);
-            assert_eq!(gs.player_hands.get(i).unwrap().must_have.len(), 0);
-
-            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&Card::SuspectCard(Suspect::Green)));
+        // Their hand is full, so they have none of the other cards
+        assert_eq!(gs.player_hands[1].must_not_have.len(), 21 - hand.len());
 
+        for card in hand {
+            assert!(gs.player_hands[2].must_not_have.contains(&card));
+            assert!(gs.envelope.must_not_have.contains(&card));
         }
 
-        // p1 should not change, must have green
-        assert_eq!(gs.player_hands.get(0).unwrap().must_not_have.len(), 0);
-        assert_eq!(gs.player_hands.get(0).unwrap().must_have.len(), 1);
-
-        assert!(gs.player_hands.get(0).unwrap().must_have.contains(&Card::SuspectCard(Suspect::Green)));
-      
-        
+        // Seen both with and without a card
+        gs.observations.push(Observation { player_index: 1, card: card("Hall"), has: false });
+        assert!(gs.verify_state().is_err());
     }
 
     #[test]
-    fn test_propogate_state_if_player_must_have_other_players_must_not_have() {
+    fn test_propogate_state_possible_responders() {
 
         let player_hands = vec![
             PlayerHand::new("p1".to_owned()),
@@ -265,106 +1785,199 @@ mod tests {
             PlayerHand::new("p4".to_owned()),
         ];
 
-        let accusations = vec![
-            Accusation { 
-                accuser_player_index: 0, 
-                room: Room::Study, 
-                suspect: Suspect::Peacock, 
-                weapon: Weapon::Knife, 
-                responding_player_index: Some(3), 
-                card_shown: None
-            }
-        ];
-
         let mut gs = GameState {
-            public_cards: HashSet::new(),
-            player_hands: player_hands,
-            self_index: 0,
-            accusations: accusations,
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: vec![
+                // p2 or p3 showed p4 a card, we missed who
+                Accusation {
+                    accuser_player_index: 3,
+                    accused_cards: vec![card("Hall"), card("Knife"), card("Plum")],
+                    responding_player_index: None,
+                    card_shown: None,
+                    responses: Vec::new(),
+                    asked_players: None,
+                    possible_responders: vec![1, 2],
+                },
+            ],
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
         };
 
         propagate_state(&mut gs).unwrap();
 
-        for i in vec![0, 3] {
-            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 0);
-            assert_eq!(gs.player_hands.get(i).unwrap().must_have.len(), 0);
-        }
+        // p1 was asked before either of them
+        assert!(gs.player_hands[0].must_not_have.contains(&card("Knife")));
+        assert_eq!(gs.get_deduction(&Fact::new(Holder::Player(0), card("Knife"), false)).unwrap().rule,
+            Rule::CouldNotRespond);
 
+        // Nothing is known about who it was yet
+        assert!(gs.player_hands[1].must_not_have.is_empty());
+        assert!(gs.player_hands[1].must_have_one_of.is_empty());
+        assert!(gs.player_hands[2].must_have_one_of.is_empty());
 
-        for i in 1..=2 {
-            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 3);
-            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&Card::RoomCard(Room::Study)));
-            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&Card::SuspectCard(Suspect::Peacock)));
-            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&Card::WeaponCard(Weapon::Knife)));
-        }
-        
+        // p2 has none of the cards, so p3 showed one
+        gs.player_hands[1].must_not_have.extend([card("Hall"), card("Knife"), card("Plum")]);
+        propagate_state(&mut gs).unwrap();
+
+        assert_eq!(gs.player_hands[2].must_have_one_of, vec![
+            [card("Hall"), card("Knife"), card("Plum")].into_iter().collect::<CardSet>()]);
     }
 
     #[test]
-    fn test_propogate_state_unknown_accusation_with_hand() {
+    fn test_propogate_state_possible_responders_around_a_player() {
 
-        let mut player_hands = vec![
+        let player_hands = vec![
             PlayerHand::new("p1".to_owned()),
             PlayerHand::new("p2".to_owned()),
             PlayerHand::new("p3".to_owned()),
             PlayerHand::new("p4".to_owned()),
         ];
 
+        let mut gs = GameState {
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: Some(0),
+            envelope: PlayerHand::new_envelope(),
+            accusations: vec![
+                // p3 or p1 showed p2 a card, we missed who, with p4 asked between them
+                Accusation {
+                    accuser_player_index: 1,
+                    accused_cards: vec![card("Hall"), card("Knife"), card("Plum")],
+                    responding_player_index: None,
+                    card_shown: None,
+                    responses: Vec::new(),
+                    asked_players: None,
+                    possible_responders: vec![2, 0],
+                },
+            ],
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
+        };
 
-        // p1 does not have Green or Pistol
-        let p1 = player_hands
-            .get_mut(0)
-            .unwrap();
+        propagate_state(&mut gs).unwrap();
 
-        p1.must_not_have
-            .insert(Card::SuspectCard(Suspect::Green));
+        // p4 was only asked if p3 couldn't show a card
+        assert!(gs.player_hands[2].must_not_have.is_empty());
+        assert!(!gs.player_hands[3].must_not_have.contains(&card("Hall")));
 
-        p1.must_not_have
-        .insert(Card::WeaponCard(Weapon::Pistol));
-        
+        // p3 has none of the cards, so p4 was asked and couldn't respond, and p1 showed one
+        gs.player_hands[2].must_not_have.extend([card("Hall"), card("Knife"), card("Plum")]);
+        propagate_state(&mut gs).unwrap();
 
-        let accusations = vec![
-            Accusation { 
-                accuser_player_index: 3, 
-                room: Room::Study, 
-                suspect: Suspect::Green, 
-                weapon: Weapon::Pistol, 
-                responding_player_index: Some(0), 
-                card_shown: None
-            }
+        for card in [card("Hall"), card("Knife"), card("Plum")] {
+            assert!(gs.player_hands[3].must_not_have.contains(&card));
+        }
+
+        assert_eq!(gs.player_hands[0].must_have_one_of, vec![
+            [card("Hall"), card("Knife"), card("Plum")].into_iter().collect::<CardSet>()]);
+    }
+
+    #[test]
+    fn test_propogate_state_spectating() {
+
+        let player_hands = vec![
+            PlayerHand::new("p1".to_owned()),
+            PlayerHand::new("p2".to_owned()),
+            PlayerHand::new("p3".to_owned()),
         ];
 
         let mut gs = GameState {
-            public_cards: HashSet::new(),
-            player_hands: player_hands,
-            self_index: 0,
-            accusations: accusations,
+            deck: Deck::classic(),
+            public_cards: CardSet::new(),
+            player_hands,
+            self_index: None,
+            envelope: PlayerHand::new_envelope(),
+            accusations: vec![
+                // p3 showed p1 a card in secret
+                Accusation {
+                    accuser_player_index: 0,
+                    accused_cards: vec![card("Hall"), card("Knife"), card("Plum")],
+                    responding_player_index: Some(2),
+                    card_shown: None,
+                    responses: Vec::new(),
+                    asked_players: None,
+                    possible_responders: Vec::new(),
+                },
+                // Nobody could show p2 a card
+                Accusation {
+                    accuser_player_index: 1,
+                    accused_cards: vec![card("Hall"), card("Rope"), card("Green")],
+                    responding_player_index: None,
+                    card_shown: None,
+                    responses: Vec::new(),
+                    asked_players: None,
+                    possible_responders: Vec::new(),
+                },
+            ],
+            final_accusations: Vec::new(),
+            observations: Vec::new(),
+            deductions: Vec::new(),
+            rules: Default::default(),
+            reveal: None,
         };
 
+        // Nobody's hand needs to be known
+        gs.verify_state().unwrap();
+
         propagate_state(&mut gs).unwrap();
+        gs.verify_state().unwrap();
 
+        assert!(gs.player_hands[1].must_not_have.contains(&card("Knife")));
 
-        // Check that p1 must have Study
-        assert_eq!(gs.player_hands.get(0).unwrap().must_not_have.len(), 2);
-        assert_eq!(gs.player_hands.get(0).unwrap().must_have.len(), 1);
+        // p3 doesn't have the Hall, so showed the Knife or Plum
+        assert!(gs.player_hands[2].must_not_have.contains(&card("Hall")));
+        assert_eq!(gs.player_hands[2].must_have_one_of, vec![
+            [card("Knife"), card("Plum")].into_iter().collect::<CardSet>()]);
 
-        assert!(gs.player_hands.get(0).unwrap().must_have.contains(&Card::RoomCard(Room::Study)));
+        // p3 and p1 couldn't respond to p2, so only p2 or the envelope has the Rope
+        assert!(gs.player_hands[0].must_not_have.contains(&card("Rope")));
+        assert!(!gs.player_hands[1].must_not_have.contains(&card("Rope")));
+    }
 
+    #[test]
+    fn test_get_holder_groups() {
+        // Every group of three holders
+        assert_eq!(get_holder_groups(0b111, 0), vec![0b001, 0b011, 0b111, 0b101, 0b010, 0b110, 0b100]);
 
-        // Check that others must not have Study (since p1 has it)
-        for i in vec![1,2,3] {
-            assert_eq!(gs.player_hands.get(i).unwrap().must_not_have.len(), 1);
-            assert_eq!(gs.player_hands.get(i).unwrap().must_have.len(), 0);
+        // Only those with the first
+        assert_eq!(get_holder_groups(0b111, 0b001), vec![0b001, 0b011, 0b111, 0b101]);
 
-            assert!(gs.player_hands.get(i).unwrap().must_not_have.contains(&Card::RoomCard(Room::Study)));
-        }
+        // Every holder included already
+        assert_eq!(get_holder_groups(0b111, 0b111), vec![0b111]);
     }
-    
+
+    #[test]
+    fn test_apply_open_slot_counting_with_many_players() {
+        let player_hands = |number_of_players: usize| -> Vec<PlayerHand> {
+            (0..number_of_players).map(|idx| PlayerHand::new(format!("p{}", idx + 1))).collect()
+        };
+
+        // Thirteen hands and three envelope categories is as many as are checked
+        let mut gs = GameState::new_spectated_game_state(Deck::classic(), player_hands(13), CardSet::new(), Default::default());
+        assert!(apply_open_slot_counting(&mut gs).is_ok());
+
+        // One more is refused, rather than only checking some of the groups
+        let mut gs = GameState::new_spectated_game_state(Deck::classic(), player_hands(14), CardSet::new(), Default::default());
+        assert!(matches!(apply_open_slot_counting(&mut gs), Err(Error(ErrorKind::TooManyOpenHolders(17, 16), _))));
+        assert!(matches!(propagate_state(&mut gs), Err(Error(ErrorKind::TooManyOpenHolders(17, 16), _))));
+    }
+
     #[test]
     fn test_get_responding_players() {
         let players_between = get_responding_players(0, Some(4), 5);
 
-        let expected_result = vec![1,2,3];
+        let expected_result = [1,2,3];
 
         assert_eq!(players_between.len(), expected_result.len());
 
@@ -378,7 +1991,7 @@ mod tests {
     fn test_get_responding_players_wrap() {
         let players_between = get_responding_players(3, Some(1), 5);
 
-        let expected_result = vec![4,0];
+        let expected_result = [4,0];
 
         assert_eq!(players_between.len(), expected_result.len());
 
@@ -392,7 +2005,7 @@ mod tests {
     fn test_get_responding_players_none() {
         let players_between = get_responding_players(3,None, 5);
 
-        let expected_result = vec![4,0,1,2];
+        let expected_result = [4,0,1,2];
 
         assert_eq!(players_between.len(), expected_result.len());
 
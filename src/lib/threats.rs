@@ -0,0 +1,129 @@
+use crate::game_state::GameState;
+use crate::solver::{propagate_state, get_potentially_winning_cards};
+use crate::errors::*;
+
+/// Finds what an opponent has at least deduced, by solving the game as they
+/// see it.
+///
+/// They see everything public, along with the cards shown to them and by
+/// them, while the cards shown between other players are left unknown. Of
+/// their own hand, they're only assumed to know what we already know, so
+/// they know everything in the view and possibly more. Observations are
+/// assumed to have been seen by everyone.
+pub fn get_opponent_view_lower_bound(gs: &GameState, player_idx: usize) -> Result<GameState> {
+    let mut view = gs.clone();
+    view.self_index = None;
+    view.reveal = None;
+    view.deductions.clear();
+
+    for holder in view.get_holders() {
+        let hand = view.get_hand_mut(holder);
+        hand.must_have.clear();
+        hand.must_not_have.clear();
+        hand.must_have_one_of.clear();
+    }
+
+    // Whatever we know of their hand, they know too
+    view.player_hands[player_idx].must_have = gs.player_hands[player_idx].must_have;
+    view.player_hands[player_idx].must_not_have = gs.player_hands[player_idx].must_not_have;
+
+    for acc in view.accusations.iter_mut() {
+        let is_accuser = acc.accuser_player_index == player_idx;
+
+        if !is_accuser && acc.responding_player_index != Some(player_idx) {
+            acc.card_shown = None;
+        }
+
+        for response in acc.responses.iter_mut() {
+            if !is_accuser && response.player_index != player_idx {
+                response.card_shown = None;
+            }
+        }
+    }
+
+    propagate_state(&mut view)?;
+
+    Ok(view)
+}
+
+/// Most envelopes an opponent could still have left, counted from what
+/// they've at least deduced. They may well have ruled out more.
+pub fn count_opponent_candidate_envelopes_upper_bound(gs: &GameState, player_idx: usize) -> Result<usize> {
+    Ok(count_candidate_envelopes(&get_opponent_view_lower_bound(gs, player_idx)?))
+}
+
+/// Number of ways the envelope could still be filled, one card from each
+/// category out of the cards that could be in it.
+pub fn count_candidate_envelopes(gs: &GameState) -> usize {
+    let potentially_winning_cards = get_potentially_winning_cards(gs);
+
+    (0..gs.deck.get_number_of_categories())
+        .map(|category_index| (&gs.deck.get_all_cards_in_category(category_index) & &potentially_winning_cards).len())
+        .product()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::find_wrong_facts;
+    use crate::simulation::simulate_game;
+
+    use super::*;
+
+    #[test]
+    fn test_get_opponent_view_lower_bound() {
+        for (number_of_players, seed) in [(3, 131), (4, 132), (5, 133)] {
+            let mut gs = simulate_game(number_of_players, seed, 15);
+            let reveal = gs.reveal.clone().unwrap();
+
+            propagate_state(&mut gs).unwrap();
+
+            for player_idx in 1..number_of_players {
+                let view = get_opponent_view_lower_bound(&gs, player_idx).unwrap();
+
+                // Never knows anything wrong, or anything we don't
+                assert!(find_wrong_facts(&view, &reveal).is_empty());
+                assert!(count_candidate_envelopes(&view) >= count_candidate_envelopes(&gs));
+                assert_eq!(count_opponent_candidate_envelopes_upper_bound(&gs, player_idx).unwrap(),
+                    count_candidate_envelopes(&view));
+
+                for holder in view.get_holders() {
+                    assert!(view.get_hand(holder).must_have.is_subset(&gs.get_hand(holder).must_have));
+                    assert!(view.get_hand(holder).must_not_have.is_subset(&gs.get_hand(holder).must_not_have));
+                }
+
+                // Knows every card we showed them, and none we showed anyone else
+                for (acc, view_acc) in gs.accusations.iter().zip(view.accusations.iter()) {
+                    if acc.responding_player_index != Some(0) {
+                        continue;
+                    }
+
+                    if acc.accuser_player_index == player_idx {
+                        assert!(view.player_hands[0].must_have.contains(&acc.card_shown.unwrap()));
+                    } else {
+                        assert_eq!(view_acc.card_shown, None);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_count_candidate_envelopes() {
+        let mut gs = simulate_game(4, 134, 0);
+        propagate_state(&mut gs).unwrap();
+
+        let remaining: usize = (0..3)
+            .map(|category_index| (&gs.deck.get_all_cards_in_category(category_index)
+                - &(&gs.player_hands[0].must_have | &gs.public_cards)).len())
+            .product();
+
+        assert_eq!(count_candidate_envelopes(&gs), remaining);
+
+        // Down to one card of each category once it's solved
+        gs.envelope.must_have = gs.reveal.clone().unwrap().envelope;
+
+        propagate_state(&mut gs).unwrap();
+        assert_eq!(count_candidate_envelopes(&gs), 1);
+    }
+}